@@ -1,18 +1,45 @@
+use core::{
+    convert::Infallible,
+    fmt::{self, Debug, Write},
+    marker::PhantomData,
+};
+
+#[cfg(not(test))]
 use x86_64::instructions::port::Port;
 
 use crate::{
-    error::ControllerError,
+    diagnostics::ScancodeDiagnostics,
+    error::{ControllerError, KeyboardError, StatusError},
     flags::{
         ControllerConfigFlags, ControllerStatusFlags, InputPortFlags, OutputPortFlags,
         TestPortFlags,
     },
-    keyboard::Keyboard,
-    mouse::Mouse,
+    io::{IoDirection, IoPort},
+    keyboard::{ascii::AsciiKey, untranslate_scancode_set, Keyboard},
+    mouse::{Mouse, MouseType},
+    presence::{HasKeyboard, HasMouse, NoKeyboard, NoMouse},
+    scancodes::{set1, set2},
+    time_source::{CycleCounter, TimeSource},
+    COMMAND_ACKNOWLEDGED,
 };
 
+#[cfg(test)]
+use crate::mock::Port;
+
+pub use self::{
+    data_source::DataSource, init_result::InitResult, internal_ram_byte::InternalRamByte,
+    ram::ControllerRam,
+};
+
+mod data_source;
+mod init_result;
+mod internal_ram_byte;
+mod ram;
+
 const DATA_REGISTER: u16 = 0x60;
 const COMMAND_REGISTER: u16 = 0x64;
 const DEFAULT_TIMEOUT: usize = 10_000;
+const BENCHMARK_SAFETY_FACTOR: usize = 100;
 
 type Result<T> = core::result::Result<T, ControllerError>;
 
@@ -47,11 +74,37 @@ pub(crate) enum Command {
 /// of this emulation is usually different from manufacturer to manufacturer and cannot always be
 /// relied upon to perform correctly. Therefore, if you're writing an operating system, you should
 /// disable this legacy support once the USB controller has been initialized.
+///
+/// IO timeouts are measured using a [`TimeSource`], which by default is a [`CycleCounter`] that
+/// counts polling attempts.
+///
+/// The `K` and `M` parameters record whether a keyboard and mouse may be attached. A controller
+/// starts out with [`HasKeyboard`] and [`HasMouse`], and can be narrowed using
+/// [`Controller::without_keyboard`] and [`Controller::without_mouse`] when a device is known not to
+/// exist. This removes access to that device's handle at compile time.
 #[derive(Debug)]
-pub struct Controller {
+pub struct Controller<T = CycleCounter, K = HasKeyboard, M = HasMouse> {
     command_register: Port<u8>,
     data_register: Port<u8>,
+    command_port: u16,
+    data_port: u16,
     timeout: usize,
+    time_source: T,
+    output_port_cache: Option<OutputPortFlags>,
+    config_cache: Option<ControllerConfigFlags>,
+    last_status: Option<ControllerStatusFlags>,
+    input_empty: bool,
+    parity_retries: usize,
+    skipped_bytes: usize,
+    mouse_type: MouseType,
+    strict: bool,
+    trace: Option<fn(IoDirection, IoPort, u8)>,
+    write_timeouts: usize,
+    wedge_threshold: Option<usize>,
+    alternate_acks: &'static [u8],
+    saw_break_prefix: bool,
+    saw_extended_prefix: bool,
+    devices: PhantomData<(K, M)>,
 }
 
 impl Controller {
@@ -73,69 +126,574 @@ impl Controller {
     /// Ensure that IO ports `0x60` and `0x64` are not accessed by any other code, and that only
     /// one `Controller` accesses those ports at any point in time.
     pub const unsafe fn with_timeout(timeout: usize) -> Self {
+        Self::with_ports(DATA_REGISTER, COMMAND_REGISTER, timeout)
+    }
+
+    /// Like `with_timeout`, but allows specifying the IO ports of the data and command registers,
+    /// for platforms that map the controller to non-standard addresses.
+    ///
+    /// # Safety
+    ///
+    /// Ensure that the given IO ports are not accessed by any other code, and that only one
+    /// `Controller` accesses those ports at any point in time.
+    pub const unsafe fn with_ports(data_port: u16, command_port: u16, timeout: usize) -> Self {
+        Self::from_parts(data_port, command_port, timeout, CycleCounter::new())
+    }
+}
+
+impl<T: TimeSource> Controller<T> {
+    /// Like `with_timeout`, but measures the IO timeout using the given [`TimeSource`]. The
+    /// timeout is then the number of cycles, as counted by the time source, that an IO operation
+    /// will be attempted for before returning [`ControllerError::Timeout`].
+    ///
+    /// # Safety
+    ///
+    /// Ensure that IO ports `0x60` and `0x64` are not accessed by any other code, and that only
+    /// one `Controller` accesses those ports at any point in time.
+    pub const unsafe fn with_time_source(timeout: usize, time_source: T) -> Self {
+        Self::from_parts(DATA_REGISTER, COMMAND_REGISTER, timeout, time_source)
+    }
+
+    const unsafe fn from_parts(
+        data_port: u16,
+        command_port: u16,
+        timeout: usize,
+        time_source: T,
+    ) -> Self {
         Self {
-            command_register: Port::new(COMMAND_REGISTER),
-            data_register: Port::new(DATA_REGISTER),
+            command_register: Port::new(command_port),
+            data_register: Port::new(data_port),
+            command_port,
+            data_port,
             timeout,
+            time_source,
+            output_port_cache: None,
+            config_cache: None,
+            last_status: None,
+            input_empty: false,
+            parity_retries: 0,
+            skipped_bytes: 0,
+            mouse_type: MouseType::Standard,
+            strict: false,
+            trace: None,
+            write_timeouts: 0,
+            wedge_threshold: None,
+            alternate_acks: &[],
+            saw_break_prefix: false,
+            saw_extended_prefix: false,
+            devices: PhantomData,
         }
     }
+}
 
+impl<T: TimeSource, M> Controller<T, HasKeyboard, M> {
     /// Obtain a handle to the keyboard.
-    pub const fn keyboard(&mut self) -> Keyboard<'_> {
+    pub const fn keyboard(&mut self) -> Keyboard<'_, T, M> {
         Keyboard::new(self)
     }
 
+    /// Compare the scancode set used by the keyboard with the one software will receive after the
+    /// controller's scancode translation, if any. See [`ScancodeDiagnostics`].
+    pub fn scancode_diagnostics(
+        &mut self,
+    ) -> core::result::Result<ScancodeDiagnostics, KeyboardError> {
+        let translation_enabled = self
+            .read_config()?
+            .contains(ControllerConfigFlags::ENABLE_TRANSLATE);
+        let keyboard_set = untranslate_scancode_set(self.keyboard().get_scancode_set()?);
+        Ok(ScancodeDiagnostics {
+            translation_enabled,
+            keyboard_set,
+        })
+    }
+
+    // Keyboard handles borrow the controller, so scancode state that must outlive one lives here
+    pub(crate) fn take_break_prefix(&mut self) -> bool {
+        core::mem::replace(&mut self.saw_break_prefix, false)
+    }
+
+    pub(crate) fn set_break_prefix(&mut self) {
+        self.saw_break_prefix = true;
+    }
+
+    pub(crate) fn take_extended_prefix(&mut self) -> bool {
+        core::mem::replace(&mut self.saw_extended_prefix, false)
+    }
+
+    pub(crate) fn set_extended_prefix(&mut self) {
+        self.saw_extended_prefix = true;
+    }
+
+    /// Mark this controller as having no keyboard attached.
+    pub fn without_keyboard(self) -> Controller<T, NoKeyboard, M> {
+        Controller {
+            command_register: self.command_register,
+            data_register: self.data_register,
+            command_port: self.command_port,
+            data_port: self.data_port,
+            timeout: self.timeout,
+            time_source: self.time_source,
+            output_port_cache: self.output_port_cache,
+            config_cache: self.config_cache,
+            last_status: self.last_status,
+            input_empty: self.input_empty,
+            parity_retries: self.parity_retries,
+            skipped_bytes: self.skipped_bytes,
+            mouse_type: self.mouse_type,
+            strict: self.strict,
+            trace: self.trace,
+            write_timeouts: self.write_timeouts,
+            wedge_threshold: self.wedge_threshold,
+            alternate_acks: self.alternate_acks,
+            saw_break_prefix: self.saw_break_prefix,
+            saw_extended_prefix: self.saw_extended_prefix,
+            devices: PhantomData,
+        }
+    }
+}
+
+impl<T: TimeSource, K> Controller<T, K, HasMouse> {
     /// Obtain a handle to the mouse.
-    pub const fn mouse(&mut self) -> Mouse<'_> {
+    pub const fn mouse(&mut self) -> Mouse<'_, T, K> {
         Mouse::new(self)
     }
 
+    /// The number of bytes in each movement data packet sent by the mouse, based on the
+    /// [`MouseType`] it last reported.
+    ///
+    /// The type is recorded whenever it's read with [`Mouse::get_mouse_type`] or
+    /// [`Mouse::reset_and_self_test`], so this reflects any extensions enabled since. Until then,
+    /// the mouse is assumed to send standard 3-byte packets. [`Mouse::read_data_packet`] and
+    /// [`Mouse::wait_for_data_packet`] read packets of this size.
+    pub const fn mouse_packet_size(&self) -> usize {
+        self.mouse_type.packet_size()
+    }
+
+    pub(crate) const fn mouse_type(&self) -> MouseType {
+        self.mouse_type
+    }
+
+    pub(crate) fn set_mouse_type(&mut self, mouse_type: MouseType) {
+        self.mouse_type = mouse_type;
+    }
+
+    /// Mark this controller as having no mouse attached.
+    pub fn without_mouse(self) -> Controller<T, K, NoMouse> {
+        Controller {
+            command_register: self.command_register,
+            data_register: self.data_register,
+            command_port: self.command_port,
+            data_port: self.data_port,
+            timeout: self.timeout,
+            time_source: self.time_source,
+            output_port_cache: self.output_port_cache,
+            config_cache: self.config_cache,
+            last_status: self.last_status,
+            input_empty: self.input_empty,
+            parity_retries: self.parity_retries,
+            skipped_bytes: self.skipped_bytes,
+            mouse_type: self.mouse_type,
+            strict: self.strict,
+            trace: self.trace,
+            write_timeouts: self.write_timeouts,
+            wedge_threshold: self.wedge_threshold,
+            alternate_acks: self.alternate_acks,
+            saw_break_prefix: self.saw_break_prefix,
+            saw_extended_prefix: self.saw_extended_prefix,
+            devices: PhantomData,
+        }
+    }
+}
+
+impl<T: TimeSource, K, M> Controller<T, K, M> {
+    /// The address of the IO port used for the data register.
+    pub const fn data_port_addr(&self) -> u16 {
+        self.data_port
+    }
+
+    /// The address of the IO port used for the command and status registers.
+    pub const fn command_port_addr(&self) -> u16 {
+        self.command_port
+    }
+
+    /// The IO timeout, in cycles as counted by the [`TimeSource`].
+    pub const fn timeout(&self) -> usize {
+        self.timeout
+    }
+
+    /// Change the IO timeout, in cycles as counted by the [`TimeSource`].
+    pub fn set_timeout(&mut self, timeout: usize) {
+        self.timeout = timeout;
+    }
+
+    /// Measure how many cycles it takes to read the configuration byte, and suggest an IO timeout
+    /// based on it, with a large margin for slower operations such as device commands.
+    ///
+    /// The suggested timeout can be applied using [`Controller::set_timeout`]. The current timeout
+    /// is still used during the measurement, so it should be generous.
+    pub fn benchmark_io(&mut self) -> Result<usize> {
+        let start = self.time_source.elapsed_cycles();
+        self.read_config()?;
+        let elapsed = self.time_source.elapsed_cycles().wrapping_sub(start) as usize;
+        Ok(elapsed.max(1).saturating_mul(BENCHMARK_SAFETY_FACTOR))
+    }
+
     /// Read the status register of the controller.
     pub fn read_status(&mut self) -> ControllerStatusFlags {
-        ControllerStatusFlags::from_bits_truncate(unsafe { self.command_register.read() })
+        let status = ControllerStatusFlags::from_bits_truncate(self.read_register(IoPort::Command));
+        self.last_status = Some(status);
+        self.input_empty = !status.contains(ControllerStatusFlags::INPUT_FULL);
+        status
+    }
+
+    /// Like [`Controller::read_status`], but returns a [`StatusError`] if either of the error bits
+    /// is set. See [`ControllerStatusFlags::parse`].
+    pub fn read_status_checked(
+        &mut self,
+    ) -> core::result::Result<ControllerStatusFlags, StatusError> {
+        let status = self.read_status();
+        ControllerStatusFlags::parse(status.bits())
+    }
+
+    /// The status most recently read from the status register, either by
+    /// [`Controller::read_status`] or while waiting to read or write data.
+    ///
+    /// This reflects the last observed status, not the current state of the hardware, but avoids
+    /// another IO access when the status was just read. For example, after
+    /// [`Controller::read_data`] returns, this is the status that showed the data buffer was full.
+    pub const fn last_status(&self) -> Option<ControllerStatusFlags> {
+        self.last_status
+    }
+
+    /// Set a function to be called with every byte read from or written to the controller's IO
+    /// ports, or `None` to stop tracing. Tracing is disabled by default.
+    ///
+    /// This is useful for logging the exact traffic with the controller when debugging. Note that
+    /// every status register read is traced too, including those made while polling.
+    pub fn set_trace(&mut self, trace: Option<fn(IoDirection, IoPort, u8)>) {
+        self.trace = trace;
+    }
+
+    /// Set the number of consecutive write timeouts after which the controller is considered
+    /// wedged, or `None` to never consider it wedged. This is disabled by default.
+    ///
+    /// If the input buffer is stuck full, every write would otherwise spin for the full IO
+    /// timeout. Once the controller is wedged, writes fail immediately with
+    /// [`ControllerError::ControllerWedged`] until [`Controller::clear_wedged`] is called.
+    pub fn set_wedge_threshold(&mut self, threshold: Option<usize>) {
+        self.wedge_threshold = threshold;
+    }
+
+    /// Whether enough consecutive writes have timed out for the controller to be considered
+    /// wedged. See [`Controller::set_wedge_threshold`].
+    pub fn is_wedged(&self) -> bool {
+        matches!(self.wedge_threshold, Some(threshold) if self.write_timeouts >= threshold)
+    }
+
+    /// Reset the count of consecutive write timeouts, so that writes are attempted again.
+    pub fn clear_wedged(&mut self) {
+        self.write_timeouts = 0;
+    }
+
+    /// Set additional bytes that devices may send to acknowledge a command, besides the standard
+    /// `0xFA`. By default, no other bytes are accepted.
+    ///
+    /// This is an escape hatch for buggy USB legacy emulation that sometimes sends the wrong
+    /// acknowledgement byte. Use it with care: a byte accepted here can no longer be reported as
+    /// an invalid response, so genuine errors or stray input might be mistaken for an
+    /// acknowledgement.
+    pub fn set_alternate_acks(&mut self, acks: &'static [u8]) {
+        self.alternate_acks = acks;
+    }
+
+    pub(crate) fn is_ack(&self, byte: u8) -> bool {
+        byte == COMMAND_ACKNOWLEDGED || self.alternate_acks.contains(&byte)
+    }
+
+    fn read_register(&mut self, port: IoPort) -> u8 {
+        let value = unsafe {
+            match port {
+                IoPort::Data => self.data_register.read(),
+                IoPort::Command => self.command_register.read(),
+            }
+        };
+        if let Some(trace) = self.trace {
+            trace(IoDirection::Read, port, value);
+        }
+        value
+    }
+
+    fn write_register(&mut self, port: IoPort, value: u8) {
+        unsafe {
+            match port {
+                IoPort::Data => self.data_register.write(value),
+                IoPort::Command => self.command_register.write(value),
+            }
+        }
+        if let Some(trace) = self.trace {
+            trace(IoDirection::Write, port, value);
+        }
+    }
+
+    pub(crate) fn elapsed_cycles(&mut self) -> u64 {
+        self.time_source.elapsed_cycles()
+    }
+
+    fn timed_out(&mut self, start: u64) -> bool {
+        self.time_source.elapsed_cycles().wrapping_sub(start) >= self.timeout as u64
     }
 
     fn wait_for_read(&mut self) -> Result<()> {
-        let mut cycles = 0;
-        while cycles < self.timeout {
+        let start = self.time_source.elapsed_cycles();
+        loop {
             if self
                 .read_status()
                 .contains(ControllerStatusFlags::OUTPUT_FULL)
             {
                 return Ok(());
             }
-            cycles += 1;
+            if self.timed_out(start) {
+                return Err(ControllerError::Timeout);
+            }
+        }
+    }
+
+    // Discards any bytes not sent by the mouse while waiting
+    fn wait_for_read_from_mouse(&mut self) -> Result<()> {
+        let start = self.time_source.elapsed_cycles();
+        loop {
+            let status = self.read_status();
+            if status.contains(ControllerStatusFlags::OUTPUT_FULL) {
+                if status.contains(ControllerStatusFlags::MOUSE_OUTPUT_FULL) {
+                    return Ok(());
+                }
+                self.read_register(IoPort::Data);
+                self.skipped_bytes = self.skipped_bytes.wrapping_add(1);
+            }
+            if self.timed_out(start) {
+                return Err(ControllerError::Timeout);
+            }
         }
-        Err(ControllerError::Timeout)
     }
 
+    // Every write must be preceded by a call to this function, since it assumes the input buffer is
+    // full again afterwards
     fn wait_for_write(&mut self) -> Result<()> {
-        let mut cycles = 0;
-        while cycles < self.timeout {
+        if self.is_wedged() {
+            return Err(ControllerError::ControllerWedged);
+        }
+        // Only our own writes fill the input buffer, so if it has been seen empty since the last
+        // write, such as while waiting for a device's response, it must still be empty
+        if core::mem::replace(&mut self.input_empty, false) {
+            return Ok(());
+        }
+        let start = self.time_source.elapsed_cycles();
+        loop {
             if !self
                 .read_status()
                 .contains(ControllerStatusFlags::INPUT_FULL)
             {
+                self.input_empty = false;
+                self.write_timeouts = 0;
                 return Ok(());
             }
-            cycles += 1;
+            if self.timed_out(start) {
+                self.write_timeouts = self.write_timeouts.saturating_add(1);
+                return Err(ControllerError::Timeout);
+            }
         }
-        Err(ControllerError::Timeout)
     }
 
     pub(crate) fn write_command(&mut self, command: Command) -> Result<()> {
         self.wait_for_write()?;
-        unsafe { self.command_register.write(command as u8) };
+        self.write_register(IoPort::Command, command as u8);
         Ok(())
     }
 
+    pub(crate) fn write_command_with_data(&mut self, command: Command, data: u8) -> Result<()> {
+        self.send_command_and_data(command as u8, data)
+    }
+
+    /// Write a raw command byte to the controller, followed by a parameter byte.
+    ///
+    /// This is useful for parameterized commands not otherwise exposed by this type. Each byte is
+    /// written once the input buffer is empty.
+    pub fn send_command_and_data(&mut self, command: u8, data: u8) -> Result<()> {
+        self.wait_for_write()?;
+        self.write_register(IoPort::Command, command);
+        self.write_data(data)
+    }
+
+    /// Write any byte to the command register once the input buffer is empty.
+    ///
+    /// This allows sending vendor-specific commands not covered by this type. The documented 8042
+    /// commands fall into the following ranges:
+    ///
+    /// - `0x20..=0x3f` and `0x60..=0x7f`: read and write internal RAM
+    /// - `0xa7..=0xae`: device enable/disable, self-tests, and diagnostic dump
+    /// - `0xc0..=0xc2`: input port access
+    /// - `0xd0..=0xd4`: output port access and device buffer writes
+    /// - `0xe0`: read test port
+    /// - `0xf0..=0xff`: pulse output port
+    ///
+    /// Other ranges are reserved or vendor-specific. For example, many AMI and Phoenix controllers
+    /// implement password and version commands in `0xa0..=0xa6`.
+    ///
+    /// # Safety
+    ///
+    /// Unknown commands may have arbitrary effects, including resetting the system or leaving the
+    /// controller waiting for parameter bytes. Ensure the command is supported by the controller
+    /// and that any bytes it expects are written or read afterwards.
+    pub unsafe fn write_command_raw(&mut self, command: u8) -> Result<()> {
+        self.wait_for_write()?;
+        self.write_register(IoPort::Command, command);
+        Ok(())
+    }
+
+    /// Write any byte to the command register, then read the byte the controller responds with.
+    ///
+    /// # Safety
+    ///
+    /// The same requirements as for [`Controller::write_command_raw`] apply. Additionally, the
+    /// command must produce exactly one response byte, or the remaining bytes must be read
+    /// afterwards.
+    pub unsafe fn read_data_after_command(&mut self, command: u8) -> Result<u8> {
+        self.write_command_raw(command)?;
+        self.read_data()
+    }
+
     /// Read a byte from the data buffer once it is full.
     ///
     /// If there is no data available to read within the configured timeout, this will return
-    /// [`ControllerError::Timeout`].
+    /// [`ControllerError::Timeout`]. If parity retries are enabled using
+    /// [`Controller::set_parity_retries`], bytes received with a parity error are discarded.
     pub fn read_data(&mut self) -> Result<u8> {
-        self.wait_for_read()?;
-        Ok(unsafe { self.data_register.read() })
+        let mut retries = 0;
+        loop {
+            self.wait_for_read()?;
+            let data = self.read_register(IoPort::Data);
+            let parity_error = self
+                .last_status
+                .is_some_and(|status| status.contains(ControllerStatusFlags::PARITY_ERR));
+            if self.parity_retries == 0 || !parity_error {
+                return Ok(data);
+            }
+            if retries == self.parity_retries {
+                return Err(ControllerError::ParityError);
+            }
+            retries += 1;
+        }
+    }
+
+    /// Set how many bytes with parity errors [`Controller::read_data`] may discard while waiting
+    /// for a clean byte, before returning [`ControllerError::ParityError`]. This is useful for
+    /// noisy lines, where the device or controller resends corrupted bytes.
+    ///
+    /// This is disabled by default, or when set to 0, in which case bytes are returned regardless
+    /// of parity errors.
+    pub fn set_parity_retries(&mut self, retries: usize) {
+        self.parity_retries = retries;
+    }
+
+    /// Enable or disable strict mode, which is disabled by default.
+    ///
+    /// In strict mode, reading the response to a controller command returns
+    /// [`ControllerError::UnexpectedMouseData`] if the byte in the buffer came from the mouse. This
+    /// catches a common source of desynchronization during initialization, where a byte sent by the
+    /// mouse is mistaken for the controller's response.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    /// Read the controller's response to a command, checking its source in strict mode.
+    fn read_response(&mut self) -> Result<u8> {
+        let data = self.read_data()?;
+        let from_mouse = self
+            .last_status
+            .is_some_and(|status| status.contains(ControllerStatusFlags::MOUSE_OUTPUT_FULL));
+        if self.strict && from_mouse {
+            return Err(ControllerError::UnexpectedMouseData(data));
+        }
+        Ok(data)
+    }
+
+    /// Read a byte from the data buffer once it holds data from the mouse. Bytes from other
+    /// sources found in the buffer first are discarded.
+    pub(crate) fn read_mouse_data(&mut self) -> Result<u8> {
+        self.wait_for_read_from_mouse()?;
+        Ok(self.read_register(IoPort::Data))
+    }
+
+    /// Discard bytes from the mouse until the data buffer is empty or holds a byte from another
+    /// source. Returns the number of bytes discarded.
+    pub(crate) fn discard_mouse_data(&mut self) -> usize {
+        let mut discarded = 0;
+        while self
+            .read_status()
+            .contains(ControllerStatusFlags::OUTPUT_FULL | ControllerStatusFlags::MOUSE_OUTPUT_FULL)
+        {
+            self.read_register(IoPort::Data);
+            discarded += 1;
+        }
+        discarded
+    }
+
+    /// Wait for a byte from the keyboard and read it, without disturbing data from the mouse.
+    /// Returns `None` if the buffer stays empty until the timeout, or holds a byte from the mouse.
+    pub(crate) fn poll_keyboard_data(&mut self) -> Option<u8> {
+        let start = self.time_source.elapsed_cycles();
+        loop {
+            let status = self.read_status();
+            if status.contains(ControllerStatusFlags::OUTPUT_FULL) {
+                if status.contains(ControllerStatusFlags::MOUSE_OUTPUT_FULL) {
+                    return None;
+                }
+                return Some(self.read_register(IoPort::Data));
+            }
+            if self.timed_out(start) {
+                return None;
+            }
+        }
+    }
+
+    /// The number of bytes discarded while waiting for data from the mouse.
+    pub(crate) const fn skipped_bytes(&self) -> usize {
+        self.skipped_bytes
+    }
+
+    /// Read a byte from the data buffer if one is available, along with the device it came from.
+    ///
+    /// This is intended for interrupt handlers shared between IRQ1 and IRQ12, and should be called
+    /// in a loop until it returns `None`. The source is determined using
+    /// [`ControllerStatusFlags::MOUSE_OUTPUT_FULL`].
+    pub fn service_interrupt(&mut self) -> Option<(DataSource, u8)> {
+        let status = self.read_status();
+        if !status.contains(ControllerStatusFlags::OUTPUT_FULL) {
+            return None;
+        }
+        let source = if status.contains(ControllerStatusFlags::MOUSE_OUTPUT_FULL) {
+            DataSource::Mouse
+        } else {
+            DataSource::Keyboard
+        };
+        Some((source, self.read_register(IoPort::Data)))
+    }
+
+    /// Call `f` with each byte available in the data buffer and the device it came from, until
+    /// the buffer is empty. Returns the number of bytes processed.
+    ///
+    /// This never waits for more data to arrive, so it's suitable for interrupt handlers. See
+    /// [`Controller::service_interrupt`].
+    pub fn for_each_pending_byte<F>(&mut self, mut f: F) -> usize
+    where
+        F: FnMut(u8, DataSource),
+    {
+        let mut count = 0;
+        while let Some((source, byte)) = self.service_interrupt() {
+            f(byte, source);
+            count += 1;
+        }
+        count
     }
 
     /// Write a byte to the data buffer once it is empty.
@@ -144,7 +702,7 @@ impl Controller {
     /// [`ControllerError::Timeout`].
     pub fn write_data(&mut self, data: u8) -> Result<()> {
         self.wait_for_write()?;
-        unsafe { self.data_register.write(data) };
+        self.write_register(IoPort::Data, data);
         Ok(())
     }
 
@@ -157,10 +715,8 @@ impl Controller {
         let command = Command::ReadInternalRam as u8 | byte_number & 0x1f;
         // Since we did some bit fiddling, we can't use write_command
         self.wait_for_write()?;
-        unsafe {
-            self.command_register.write(command);
-        }
-        self.read_data()
+        self.write_register(IoPort::Command, command);
+        self.read_response()
     }
 
     /// Write a byte to the controller's internal RAM.
@@ -168,38 +724,217 @@ impl Controller {
     /// The desired byte index must be between 0 and 31. Byte 0 is also known as the configuration
     /// byte or command byte.
     pub fn write_internal_ram(&mut self, byte_number: u8, data: u8) -> Result<()> {
+        if byte_number & 0x1f == 0 {
+            self.invalidate_config_cache();
+        }
         // Limit from 0 - 31, start command byte at 0x60
         let command = Command::WriteInternalRam as u8 | byte_number & 0x1f;
-        // Since we did some bit fiddling, we can't use write_command
-        self.wait_for_write()?;
-        unsafe {
-            self.command_register.write(command);
-        }
-        self.write_data(data)
+        self.send_command_and_data(command, data)
+    }
+
+    /// Read a byte from the controller's internal RAM, identified by name where its meaning is
+    /// known. See [`InternalRamByte`].
+    pub fn read_internal_ram_typed(&mut self, byte: InternalRamByte) -> Result<u8> {
+        self.read_internal_ram(byte.index())
+    }
+
+    /// Write a byte to the controller's internal RAM, identified by name where its meaning is
+    /// known. See [`InternalRamByte`].
+    pub fn write_internal_ram_typed(&mut self, byte: InternalRamByte, data: u8) -> Result<()> {
+        self.write_internal_ram(byte.index(), data)
     }
 
     /// Read the configuration byte (or command byte) of the controller. This is the same as
     /// reading byte 0 of the internal RAM.
     pub fn read_config(&mut self) -> Result<ControllerConfigFlags> {
-        Ok(ControllerConfigFlags::from_bits_truncate(
+        Ok(ControllerConfigFlags::new_masked(
             self.read_internal_ram(0)?,
         ))
     }
 
     /// Write the configuration byte (or command byte) of the controller. This is the same as
     /// writing to byte 0 of the internal RAM.
+    ///
+    /// All flag types can be constructed in a `const` context, so a fixed configuration can be
+    /// defined at compile time:
+    ///
+    /// ```no_run
+    /// use ps2::{flags::ControllerConfigFlags, Controller};
+    ///
+    /// const CONFIG: ControllerConfigFlags = ControllerConfigFlags::ENABLE_KEYBOARD_INTERRUPT
+    ///     .union(ControllerConfigFlags::ENABLE_MOUSE_INTERRUPT);
+    ///
+    /// let mut controller = unsafe { Controller::new() };
+    /// controller.write_config(CONFIG).unwrap();
+    /// ```
     pub fn write_config(&mut self, config: ControllerConfigFlags) -> Result<()> {
         self.write_internal_ram(0, config.bits())
     }
 
-    /// Disable the mouse. Sets the [`ControllerConfigFlags::DISABLE_MOUSE`] flag.
+    /// Read the configuration byte of the controller, or return the value read previously if there
+    /// is one.
+    ///
+    /// The cache is invalidated by [`Controller::write_config`] and
+    /// [`Controller::test_controller`], and kept up to date by the commands that enable and disable
+    /// each device. If the configuration byte is changed through other means, such as
+    /// [`Controller::send_command_and_data`], call [`Controller::invalidate_config_cache`]
+    /// afterwards.
+    pub fn read_config_cached(&mut self) -> Result<ControllerConfigFlags> {
+        match self.config_cache {
+            Some(config) => Ok(config),
+            None => {
+                let config = self.read_config()?;
+                self.config_cache = Some(config);
+                Ok(config)
+            }
+        }
+    }
+
+    /// Discard the cached configuration byte, so the next call to
+    /// [`Controller::read_config_cached`] reads it from the controller.
+    pub fn invalidate_config_cache(&mut self) {
+        self.config_cache = None;
+    }
+
+    fn update_config_cache(&mut self, flag: ControllerConfigFlags, value: bool) {
+        if let Some(config) = self.config_cache.as_mut() {
+            config.set(flag, value);
+        }
+    }
+
+    /// Read the configuration byte of the controller, let `f` modify it, and write it back.
+    ///
+    /// This is the preferred way to change individual configuration flags without clobbering the
+    /// others.
+    pub fn modify_config<F: FnOnce(&mut ControllerConfigFlags)>(&mut self, f: F) -> Result<()> {
+        let mut config = self.read_config()?;
+        f(&mut config);
+        self.write_config(config)
+    }
+
+    /// Set or clear the given flags in the configuration byte, leaving the others unchanged.
+    ///
+    /// This reads the configuration byte and writes it back, so it is not atomic. If the
+    /// configuration byte may also be modified from another context, such as an interrupt handler,
+    /// callers must ensure mutual exclusion themselves.
+    pub fn set_config_flag(&mut self, flag: ControllerConfigFlags, value: bool) -> Result<()> {
+        self.modify_config(|config| config.set(flag, value))
+    }
+
+    /// Disable the mouse. Sets the [`ControllerConfigFlags::DISABLE_MOUSE`] flag, including in the
+    /// cached configuration byte.
     pub fn disable_mouse(&mut self) -> Result<()> {
-        self.write_command(Command::DisableMouse)
+        self.write_command(Command::DisableMouse)?;
+        self.update_config_cache(ControllerConfigFlags::DISABLE_MOUSE, true);
+        Ok(())
     }
 
-    /// Enable the mouse. Clears the [`ControllerConfigFlags::DISABLE_MOUSE`] flag.
+    /// Enable the mouse. Clears the [`ControllerConfigFlags::DISABLE_MOUSE`] flag, including in the
+    /// cached configuration byte.
+    ///
+    /// Controllers with only one channel leave the flag set, so don't rely on the cached flag
+    /// until [`Controller::channel_count`] has confirmed there is a second channel.
     pub fn enable_mouse(&mut self) -> Result<()> {
-        self.write_command(Command::EnableMouse)
+        self.write_command(Command::EnableMouse)?;
+        self.update_config_cache(ControllerConfigFlags::DISABLE_MOUSE, false);
+        Ok(())
+    }
+
+    /// Determine whether the controller has one or two PS/2 channels.
+    ///
+    /// The mouse is disabled first, and if [`ControllerConfigFlags::DISABLE_MOUSE`] is set
+    /// afterwards, it's enabled again to see whether the flag clears. Only a controller with a
+    /// second channel will clear it. The mouse is left disabled either way.
+    pub fn channel_count(&mut self) -> Result<u8> {
+        self.disable_mouse()?;
+        if !self
+            .read_config()?
+            .contains(ControllerConfigFlags::DISABLE_MOUSE)
+        {
+            return Ok(1);
+        }
+        self.enable_mouse()?;
+        let dual_channel = !self
+            .read_config()?
+            .contains(ControllerConfigFlags::DISABLE_MOUSE);
+        self.disable_mouse()?;
+        Ok(if dual_channel { 2 } else { 1 })
+    }
+
+    /// Initialize the controller, following the process outlined on the
+    /// [OSDev wiki](https://web.archive.org/web/20201112021519/https://wiki.osdev.org/%228042%22_PS/2_Controller#Initialising_the_PS.2F2_Controller).
+    ///
+    /// Both devices are disabled and the data buffer is flushed. Interrupts and scancode
+    /// translation are then disabled in the configuration byte, and the controller performs a
+    /// self-test. Finally, the number of channels is determined, each interface is tested, and
+    /// each working device is enabled.
+    ///
+    /// Interrupts are left disabled, so the caller can decide which ones to enable. The devices
+    /// themselves are not reset, which can be done afterwards with
+    /// [`Keyboard::reset_and_self_test`] and [`Mouse::reset_and_self_test`]. Returns an error if
+    /// the controller fails its self-test.
+    pub fn initialize(&mut self) -> Result<InitResult> {
+        self.disable_keyboard()?;
+        self.disable_mouse()?;
+        self.for_each_pending_byte(|_, _| {});
+
+        let mut config = self.read_config()?;
+        config.remove(
+            ControllerConfigFlags::ENABLE_KEYBOARD_INTERRUPT
+                | ControllerConfigFlags::ENABLE_MOUSE_INTERRUPT
+                | ControllerConfigFlags::ENABLE_TRANSLATE,
+        );
+        self.write_config(config)?;
+
+        self.with_devices_quiesced(Self::test_controller)?;
+        // Write config again in case the self-test reset the controller
+        self.write_config(config)?;
+
+        let has_mouse = self.channel_count()? == 2;
+        let keyboard = self.test_keyboard().is_ok();
+        let mouse = has_mouse && self.test_mouse().is_ok();
+
+        if keyboard {
+            self.enable_keyboard()?;
+        }
+        if mouse {
+            self.enable_mouse()?;
+        }
+        Ok(InitResult { keyboard, mouse })
+    }
+
+    /// Disable both devices and discard any pending data, run `f`, then enable the devices that
+    /// were enabled before.
+    ///
+    /// A device may send data at any time, so the next byte read after a controller command like
+    /// [`Controller::test_controller`] could come from a device instead. Quiescing the devices
+    /// first guarantees that the response to the command is read. The devices are restored even if
+    /// `f` fails, in which case the error from `f` is returned.
+    pub fn with_devices_quiesced<F, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Self) -> Result<R>,
+    {
+        self.for_each_pending_byte(|_, _| {});
+        let config = self.read_config()?;
+        self.disable_keyboard()?;
+        self.disable_mouse()?;
+        self.for_each_pending_byte(|_, _| {});
+
+        let result = f(self);
+        let restored = self.restore_devices(config);
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
+    fn restore_devices(&mut self, config: ControllerConfigFlags) -> Result<()> {
+        if !config.contains(ControllerConfigFlags::DISABLE_KEYBOARD) {
+            self.enable_keyboard()?;
+        }
+        if !config.contains(ControllerConfigFlags::DISABLE_MOUSE) {
+            self.enable_mouse()?;
+        }
+        Ok(())
     }
 
     /// Perform a self-test on the mouse.
@@ -207,7 +942,7 @@ impl Controller {
     /// Returns [`ControllerError::TestFailed`] if the test fails.
     pub fn test_mouse(&mut self) -> Result<()> {
         self.write_command(Command::TestMouse)?;
-        match self.read_data()? {
+        match self.read_response()? {
             0x00 => Ok(()),
             err => Err(ControllerError::TestFailed { response: err }),
         }
@@ -217,19 +952,38 @@ impl Controller {
     ///
     /// Returns [`ControllerError::TestFailed`] if the test fails.
     pub fn test_controller(&mut self) -> Result<()> {
+        // Some controllers reset themselves during the test
+        self.invalidate_config_cache();
         self.write_command(Command::TestController)?;
-        match self.read_data()? {
+        match self.read_response()? {
             0x55 => Ok(()),
             err => Err(ControllerError::TestFailed { response: err }),
         }
     }
 
+    /// Perform a self-test on the controller, retrying up to `max_attempts` times if it fails.
+    ///
+    /// Some controllers, particularly emulated ones and older hardware, fail the first self-test
+    /// but pass on a later attempt. Returns the 1-based number of the attempt that passed, so a
+    /// result greater than 1 indicates unreliable hardware. If every attempt fails, returns the
+    /// error from the last one. At least one attempt is always made.
+    pub fn test_controller_with_retry(&mut self, max_attempts: u8) -> Result<u8> {
+        let mut attempt = 1;
+        loop {
+            match self.test_controller() {
+                Ok(()) => return Ok(attempt),
+                Err(err) if attempt >= max_attempts => return Err(err),
+                Err(_) => attempt += 1,
+            }
+        }
+    }
+
     /// Perform a self-test on the keyboard.
     ///
     /// Returns [`ControllerError::TestFailed`] if the test fails.
     pub fn test_keyboard(&mut self) -> Result<()> {
         self.write_command(Command::TestKeyboard)?;
-        match self.read_data()? {
+        match self.read_response()? {
             0x00 => Ok(()),
             err => Err(ControllerError::TestFailed { response: err }),
         }
@@ -237,33 +991,71 @@ impl Controller {
 
     /// Dump all bytes of the controller's internal RAM.
     // TODO: Test this, eventually. I wasn't able to get it working with any of my devices
-    pub fn diagnostic_dump(&mut self) -> Result<[u8; 32]> {
+    pub fn diagnostic_dump(&mut self) -> Result<ControllerRam> {
         self.write_command(Command::DiagnosticDump)?;
         let mut result = [0; 32];
         for byte in result.iter_mut() {
-            *byte = self.read_data()?;
+            *byte = self.read_response()?;
         }
-        Ok(result)
+        Ok(ControllerRam::new(result))
+    }
+
+    /// Write a human-readable summary of the controller's state to `f`, for debugging on real
+    /// hardware.
+    ///
+    /// This includes the status register, configuration byte, output and input ports, and whether
+    /// the keyboard and mouse interfaces are enabled in the configuration byte. Values that can't
+    /// be read are written as errors rather than aborting the dump.
+    ///
+    /// Whether a keyboard or mouse is actually present is not determined, since probing a device
+    /// could disturb it. An interface can be enabled with nothing connected to it. Use
+    /// [`Keyboard::is_present`] or [`Mouse::is_present`] to check for a device.
+    pub fn dump_state(&mut self, f: &mut impl Write) -> fmt::Result {
+        writeln!(f, "status: {:?}", self.read_status())?;
+        let config = self.read_config();
+        let enabled = config.as_ref().ok().map(|config| {
+            (
+                !config.contains(ControllerConfigFlags::DISABLE_KEYBOARD),
+                !config.contains(ControllerConfigFlags::DISABLE_MOUSE),
+            )
+        });
+        write_result(f, "config", config)?;
+        let output_port = self.read_output_port();
+        write_result(f, "output port", output_port)?;
+        let input_port = self.read_input_port();
+        write_result(f, "input port", input_port)?;
+        write_enabled(
+            f,
+            "keyboard interface",
+            enabled.map(|(keyboard, _)| keyboard),
+        )?;
+        write_enabled(f, "mouse interface", enabled.map(|(_, mouse)| mouse))
     }
 
     /// Disable the keyboard.
     ///
-    /// Sets the [`ControllerConfigFlags::DISABLE_KEYBOARD`] flag.
+    /// Sets the [`ControllerConfigFlags::DISABLE_KEYBOARD`] flag, including in the cached
+    /// configuration byte.
     pub fn disable_keyboard(&mut self) -> Result<()> {
-        self.write_command(Command::DisableKeyboard)
+        self.write_command(Command::DisableKeyboard)?;
+        self.update_config_cache(ControllerConfigFlags::DISABLE_KEYBOARD, true);
+        Ok(())
     }
 
     /// Enable the keyboard.
     ///
-    /// Clears the [`ControllerConfigFlags::DISABLE_KEYBOARD`] flag.
+    /// Clears the [`ControllerConfigFlags::DISABLE_KEYBOARD`] flag, including in the cached
+    /// configuration byte.
     pub fn enable_keyboard(&mut self) -> Result<()> {
-        self.write_command(Command::EnableKeyboard)
+        self.write_command(Command::EnableKeyboard)?;
+        self.update_config_cache(ControllerConfigFlags::DISABLE_KEYBOARD, false);
+        Ok(())
     }
 
     /// Read the state of the controller's input port.
     pub fn read_input_port(&mut self) -> Result<InputPortFlags> {
         self.write_command(Command::ReadControllerInput)?;
-        Ok(InputPortFlags::from_bits_truncate(self.read_data()?))
+        Ok(InputPortFlags::from_bits_truncate(self.read_response()?))
     }
 
     /// Write the low nibble of the controller's input port to the low nibble of the controller
@@ -281,52 +1073,971 @@ impl Controller {
     /// Read the state of the controller's output port.
     pub fn read_output_port(&mut self) -> Result<OutputPortFlags> {
         self.write_command(Command::ReadControllerOutput)?;
-        Ok(OutputPortFlags::from_bits_truncate(self.read_data()?))
+        Ok(OutputPortFlags::from_bits_truncate(self.read_response()?))
+    }
+
+    /// Read the state of the controller's output port, reusing the last state read if it is still
+    /// valid.
+    ///
+    /// The cache is invalidated by [`Controller::write_output_port`]. If the output port is changed
+    /// through other means, such as [`Controller::send_command_and_data`], call
+    /// [`Controller::invalidate_output_port_cache`] afterwards.
+    pub fn read_output_port_cached(&mut self) -> Result<OutputPortFlags> {
+        match self.output_port_cache {
+            Some(output) => Ok(output),
+            None => {
+                let output = self.read_output_port()?;
+                self.output_port_cache = Some(output);
+                Ok(output)
+            }
+        }
+    }
+
+    /// Discard the cached state of the controller's output port, so the next call to
+    /// [`Controller::read_output_port_cached`] reads it from the controller.
+    pub fn invalidate_output_port_cache(&mut self) {
+        self.output_port_cache = None;
     }
 
     /// Write the state of the controller's output port.
     pub fn write_output_port(&mut self, output: OutputPortFlags) -> Result<()> {
-        self.write_command(Command::WriteControllerOutput)?;
-        self.write_data(output.bits())
+        self.invalidate_output_port_cache();
+        self.write_command_with_data(Command::WriteControllerOutput, output.bits())
     }
 
     /// Write a byte to the data buffer as if it were received from the keyboard.
     ///
     /// This will trigger an interrupt if interrupts are enabled.
     pub fn write_keyboard_buffer(&mut self, data: u8) -> Result<()> {
-        self.write_command(Command::WriteKeyboardBuffer)?;
-        self.write_data(data)
+        self.write_command_with_data(Command::WriteKeyboardBuffer, data)
+    }
+
+    /// Write a sequence of bytes to the data buffer as if they were received from the keyboard.
+    ///
+    /// This will trigger an interrupt for each byte if interrupts are enabled.
+    pub fn inject_keyboard_scancodes(&mut self, scancodes: &[u8]) -> Result<()> {
+        for &scancode in scancodes {
+            self.write_keyboard_buffer(scancode)?;
+        }
+        Ok(())
+    }
+
+    /// Simulate typing the given text on a keyboard with a US layout, by injecting a make and
+    /// break code for each character into the data buffer. Shift is pressed around uppercase
+    /// letters and symbols.
+    ///
+    /// Only scancode sets 1 and 2 are supported, and the text must consist of printable ASCII
+    /// characters. Otherwise, [`ControllerError::UnsupportedScancodeSet`] or
+    /// [`ControllerError::UnmappedCharacter`] is returned before anything is injected.
+    pub fn type_ascii(&mut self, text: &str, set: u8) -> Result<()> {
+        let shift = match set {
+            1 => set1::LEFT_SHIFT,
+            2 => set2::LEFT_SHIFT,
+            other => return Err(ControllerError::UnsupportedScancodeSet(other)),
+        };
+        if let Some(character) = text.chars().find(|&c| AsciiKey::from_char(c).is_none()) {
+            return Err(ControllerError::UnmappedCharacter(character));
+        }
+
+        for key in text.chars().filter_map(AsciiKey::from_char) {
+            let scancode = if set == 1 { key.set1 } else { key.set2 };
+            if key.shift {
+                self.inject_key(set, shift, true)?;
+            }
+            self.inject_key(set, scancode, true)?;
+            self.inject_key(set, scancode, false)?;
+            if key.shift {
+                self.inject_key(set, shift, false)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn inject_key(&mut self, set: u8, scancode: u8, pressed: bool) -> Result<()> {
+        match (set, pressed) {
+            (_, true) => self.inject_keyboard_scancodes(&[scancode]),
+            (1, false) => self.inject_keyboard_scancodes(&[set1::break_code(scancode)]),
+            (_, false) => self.inject_keyboard_scancodes(&[set2::BREAK_PREFIX, scancode]),
+        }
     }
 
     /// Write a byte to the data buffer as if it were received from the mouse.
     ///
     /// This will trigger an interrupt if interrupts are enabled.
     pub fn write_mouse_buffer(&mut self, data: u8) -> Result<()> {
-        self.write_command(Command::WriteMouseBuffer)?;
-        self.write_data(data)
+        self.write_command_with_data(Command::WriteMouseBuffer, data)
     }
 
     /// Write a byte to the mouse's data buffer.
     pub fn write_mouse(&mut self, data: u8) -> Result<()> {
-        self.write_command(Command::WriteMouse)?;
-        self.write_data(data)
+        self.write_command_with_data(Command::WriteMouse, data)
+    }
+
+    /// Write a byte to the mouse's data buffer, then read the byte it responds with.
+    ///
+    /// The response is returned as-is, so the caller must check whether it's an acknowledgement.
+    /// This is useful for sending mouse commands without going through [`Mouse`].
+    pub fn write_mouse_and_read_response(&mut self, data: u8) -> Result<u8> {
+        self.write_mouse(data)?;
+        self.read_data()
+    }
+
+    /// Read the state of the controller's output port, let `f` modify it, and write it back.
+    ///
+    /// The output port controls system reset and the A20 gate, so this is the preferred way to
+    /// change individual bits of it without clobbering the others.
+    pub fn modify_output_port<F: FnOnce(&mut OutputPortFlags)>(&mut self, f: F) -> Result<()> {
+        let mut output = self.read_output_port()?;
+        f(&mut output);
+        self.write_output_port(output)
+    }
+
+    /// Read the state of the controller's output port, compute a new state using `f`, and write it
+    /// only if it differs from the current state.
+    ///
+    /// Since the reset line is active low, a new state with [`OutputPortFlags::SYSTEM_RESET`]
+    /// cleared would reset the CPU. Unless `allow_reset` is true, this returns
+    /// [`ControllerError::ResetNotAllowed`] instead of writing such a state.
+    pub fn write_output_port_safe<F>(&mut self, f: F, allow_reset: bool) -> Result<()>
+    where
+        F: FnOnce(OutputPortFlags) -> OutputPortFlags,
+    {
+        let current = self.read_output_port()?;
+        let new = f(current);
+        if new == current {
+            return Ok(());
+        }
+        if !allow_reset && !new.contains(OutputPortFlags::SYSTEM_RESET) {
+            return Err(ControllerError::ResetNotAllowed);
+        }
+        self.write_output_port(new)
+    }
+
+    /// Check whether the keyboard and mouse interrupt lines, IRQ1 and IRQ12, are asserted
+    /// according to the controller's output port, in that order.
+    ///
+    /// These bits reflect the output port latch, which isn't necessarily the live state of the
+    /// interrupt lines on every controller. This is mostly useful for diagnosing why an interrupt
+    /// isn't firing.
+    pub fn interrupt_lines(&mut self) -> Result<(bool, bool)> {
+        let output = self.read_output_port()?;
+        Ok((
+            output.contains(OutputPortFlags::KEYBOARD_INTERRUPT),
+            output.contains(OutputPortFlags::MOUSE_INTERRUPT),
+        ))
+    }
+
+    /// Enable or disable the A20 gate using the controller's output port, then read the output
+    /// port back to check that the change took effect.
+    ///
+    /// Returns [`ControllerError::A20VerificationFailed`] if the A20 gate is in the wrong state
+    /// afterwards. Many systems also support the faster "fast A20" method of setting bit 1 of IO
+    /// port `0x92`, which this method doesn't cover.
+    pub fn configure_a20(&mut self, enable: bool) -> Result<()> {
+        self.write_output_port_safe(
+            |mut output| {
+                output.set(OutputPortFlags::A20_GATE, enable);
+                output
+            },
+            false,
+        )?;
+        if self.a20_enabled()? != enable {
+            return Err(ControllerError::A20VerificationFailed);
+        }
+        Ok(())
+    }
+
+    /// Check whether the A20 gate is enabled, according to the controller's output port.
+    pub fn a20_enabled(&mut self) -> Result<bool> {
+        Ok(self.read_output_port()?.contains(OutputPortFlags::A20_GATE))
     }
 
     /// Read the state of the controller's test port.
     pub fn read_test_port(&mut self) -> Result<TestPortFlags> {
         self.write_command(Command::ReadTestPort)?;
-        Ok(TestPortFlags::from_bits_truncate(self.read_data()?))
+        Ok(TestPortFlags::from_bits_truncate(self.read_response()?))
     }
 
-    /// Pulse the low nibble of the given byte onto the lower nibble of the controller output port.
+    /// Pulse the lower nibble of the controller output port for about 6 ms.
+    ///
+    /// The low nibble of `data` is a mask in which **cleared** bits select the lines to pulse, and
+    /// set bits leave the corresponding lines alone. The high nibble is ignored. This matches the
+    /// encoding of the `0xf0..=0xff` controller commands, so `data = 0x0f` pulses nothing and
+    /// `data = 0x00` pulses every line, including [`OutputPortFlags::SYSTEM_RESET`]. Since the
+    /// reset line is active low, pulsing it resets the CPU.
+    ///
+    /// Prefer [`Controller::pulse_output`], which takes the lines to pulse directly.
     pub fn pulse_output_low_nibble(&mut self, data: u8) -> Result<()> {
         // Make the high nibble all 1's
-        let command = Command::PulseOutput as u8 | data;
+        let command = Command::PulseOutput as u8 | (data & 0x0f);
         // Since we did some bit fiddling, we can't use write_command
         self.wait_for_write()?;
+        self.write_register(IoPort::Command, command);
+        Ok(())
+    }
+
+    /// Pulse the given lines of the controller output port low for about 6 ms.
+    ///
+    /// Only the lower nibble of the output port can be pulsed, so any flags in `lines` other than
+    /// [`OutputPortFlags::SYSTEM_RESET`] and [`OutputPortFlags::A20_GATE`] (and the mouse data and
+    /// clock lines on some controllers) are ignored. Passing an empty set pulses nothing.
+    pub fn pulse_output(&mut self, lines: OutputPortFlags) -> Result<()> {
+        self.pulse_output_low_nibble(!lines.bits())
+    }
+
+    /// Pulse only the CPU reset line, which resets the CPU.
+    ///
+    /// This is the traditional way of rebooting a PC through the PS/2 controller. It sends command
+    /// `0xfe`, which leaves the other lines untouched.
+    pub fn pulse_reset_line(&mut self) -> Result<()> {
+        self.pulse_output(OutputPortFlags::SYSTEM_RESET)
+    }
+
+    /// Spin for `delay` iterations, pulse the CPU reset line, then spin until the reset happens.
+    ///
+    /// The delay gives hardware a moment to settle, but this can't know about any pending writes.
+    /// Callers must ensure that caches are flushed and storage is quiesced before calling this.
+    ///
+    /// This only returns if pulsing the reset line fails. [`Infallible`] stands in for the never
+    /// type, which isn't stable yet.
+    pub fn initiate_system_reset(&mut self, delay: usize) -> Result<Infallible> {
+        for _ in 0..delay {
+            core::hint::spin_loop();
+        }
+        self.pulse_reset_line()?;
+        loop {
+            core::hint::spin_loop();
+        }
+    }
+}
+
+fn write_result<V: Debug>(f: &mut impl Write, label: &str, result: Result<V>) -> fmt::Result {
+    match result {
+        Ok(value) => writeln!(f, "{}: {:?}", label, value),
+        Err(err) => writeln!(f, "{}: error ({:?})", label, err),
+    }
+}
+
+fn write_enabled(f: &mut impl Write, label: &str, enabled: Option<bool>) -> fmt::Result {
+    match enabled {
+        Some(true) => writeln!(f, "{}: enabled in config", label),
+        Some(false) => writeln!(f, "{}: disabled in config", label),
+        None => writeln!(f, "{}: unknown", label),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::mock::{self, COMMAND, DATA};
+    use std::{cell::RefCell, vec::Vec};
+
+    #[test]
+    fn send_command_and_data_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        controller.send_command_and_data(0xd1, 0xdf).unwrap();
+        controller.write_internal_ram(3, 0x42).unwrap();
+        assert_eq!(
+            mock::writes(),
+            [(COMMAND, 0xd1), (DATA, 0xdf), (COMMAND, 0x63), (DATA, 0x42)]
+        );
+    }
+
+    #[test]
+    fn internal_ram_typed_test() {
+        mock::reset();
+        mock::push_data(&[0x01]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller
+                .read_internal_ram_typed(InternalRamByte::SecurityEnabled)
+                .unwrap(),
+            0x01
+        );
+        controller
+            .write_internal_ram_typed(InternalRamByte::Other(0x05), 0xaa)
+            .unwrap();
+        assert_eq!(
+            mock::writes(),
+            [(COMMAND, 0x33), (COMMAND, 0x65), (DATA, 0xaa)]
+        );
+    }
+
+    #[test]
+    fn write_mouse_and_read_response_test() {
+        mock::reset();
+        mock::push_reply(&[0xfa]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller.write_mouse_and_read_response(0xf4).unwrap(),
+            0xfa
+        );
+        assert_eq!(mock::writes(), [(COMMAND, 0xd4), (DATA, 0xf4)]);
+    }
+
+    #[test]
+    fn custom_ports_test() {
+        mock::reset();
+        mock::set_ports(0x160, 0x164);
+        mock::push_data(&[0x47]);
+        let mut controller = unsafe { Controller::with_ports(0x160, 0x164, 100) };
+        assert_eq!(controller.read_internal_ram(1).unwrap(), 0x47);
+        controller.write_data(0xff).unwrap();
+        assert_eq!(mock::writes(), [(0x164, 0x21), (0x160, 0xff)]);
+        assert_eq!(
+            (controller.data_port_addr(), controller.command_port_addr()),
+            (0x160, 0x164)
+        );
+    }
+
+    #[test]
+    fn default_port_addrs_test() {
+        let controller = unsafe { Controller::new() };
+        assert_eq!(controller.data_port_addr(), 0x60);
+        assert_eq!(controller.command_port_addr(), 0x64);
+    }
+
+    fn injected_bytes() -> Vec<u8> {
+        let writes = mock::writes();
+        assert!(writes
+            .chunks(2)
+            .all(|pair| pair[0] == (COMMAND, Command::WriteKeyboardBuffer as u8)));
+        writes.chunks(2).map(|pair| pair[1].1).collect()
+    }
+
+    #[test]
+    fn type_ascii_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        controller.type_ascii("Hi!", 1).unwrap();
+        assert_eq!(
+            injected_bytes(),
+            [0x2a, 0x23, 0xa3, 0xaa, 0x17, 0x97, 0x2a, 0x02, 0x82, 0xaa]
+        );
+
+        mock::reset();
+        controller.type_ascii("Hi!", 2).unwrap();
+        assert_eq!(
+            injected_bytes(),
+            [
+                0x12, 0x33, 0xf0, 0x33, 0xf0, 0x12, 0x43, 0xf0, 0x43, 0x12, 0x16, 0xf0, 0x16, 0xf0,
+                0x12
+            ]
+        );
+    }
+
+    #[test]
+    fn type_ascii_rejects_invalid_input_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        assert!(matches!(
+            controller.type_ascii("ok\n", 1),
+            Err(ControllerError::UnmappedCharacter('\n'))
+        ));
+        assert!(matches!(
+            controller.type_ascii("ok", 3),
+            Err(ControllerError::UnsupportedScancodeSet(3))
+        ));
+        assert!(mock::writes().is_empty());
+    }
+
+    #[test]
+    fn output_port_cache_test() {
+        mock::reset();
+        mock::push_data(&[0x03, 0x01]);
+        let mut controller = unsafe { Controller::new() };
+        let expected = OutputPortFlags::from_bits_truncate(0x03);
+        assert_eq!(controller.read_output_port_cached().unwrap(), expected);
+        assert_eq!(controller.read_output_port_cached().unwrap(), expected);
+        assert_eq!(mock::writes(), [(COMMAND, 0xd0)]);
+
+        controller.write_output_port(expected).unwrap();
+        assert_eq!(
+            controller.read_output_port_cached().unwrap(),
+            OutputPortFlags::from_bits_truncate(0x01)
+        );
+        assert_eq!(
+            mock::writes(),
+            [
+                (COMMAND, 0xd0),
+                (COMMAND, 0xd1),
+                (DATA, 0x03),
+                (COMMAND, 0xd0)
+            ]
+        );
+    }
+
+    #[test]
+    fn config_cache_test() {
+        mock::reset();
+        mock::emulate_controller(0x27);
+        let mut controller = unsafe { Controller::new() };
+        let config = controller.read_config_cached().unwrap();
+        assert!(config.contains(ControllerConfigFlags::DISABLE_MOUSE));
+
+        controller.enable_mouse().unwrap();
+        controller.disable_keyboard().unwrap();
+        let config = controller.read_config_cached().unwrap();
+        assert!(!config.contains(ControllerConfigFlags::DISABLE_MOUSE));
+        assert!(config.contains(ControllerConfigFlags::DISABLE_KEYBOARD));
+        assert_eq!(config.bits(), mock::config());
+        assert_eq!(
+            mock::writes(),
+            [(COMMAND, 0x20), (COMMAND, 0xa8), (COMMAND, 0xad)]
+        );
+
+        controller
+            .write_config(ControllerConfigFlags::empty())
+            .unwrap();
+        assert_eq!(controller.read_config_cached().unwrap().bits(), 0);
+        assert_eq!(mock::writes().last(), Some(&(COMMAND, 0x20)));
+    }
+
+    #[test]
+    fn modify_output_port_test() {
+        mock::reset();
+        mock::push_data(&[0x03]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .modify_output_port(|output| {
+                assert_eq!(output.bits(), 0x03);
+                output.set(OutputPortFlags::A20_GATE, false);
+            })
+            .unwrap();
+        assert_eq!(
+            mock::writes(),
+            [(COMMAND, 0xd0), (COMMAND, 0xd1), (DATA, 0x01)]
+        );
+    }
+
+    #[test]
+    fn modify_config_test() {
+        mock::reset();
+        mock::push_data(&[0x47]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .modify_config(|config| config.set(ControllerConfigFlags::ENABLE_TRANSLATE, false))
+            .unwrap();
+        assert_eq!(
+            mock::writes(),
+            [(COMMAND, 0x20), (COMMAND, 0x60), (DATA, 0x07)]
+        );
+    }
+
+    #[test]
+    fn set_config_flag_test() {
+        mock::reset();
+        mock::emulate_controller(0x07);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .set_config_flag(ControllerConfigFlags::ENABLE_TRANSLATE, true)
+            .unwrap();
+        assert_eq!(mock::config(), 0x47);
+        controller
+            .set_config_flag(ControllerConfigFlags::ENABLE_KEYBOARD_INTERRUPT, false)
+            .unwrap();
+        assert_eq!(mock::config(), 0x46);
+    }
+
+    #[test]
+    fn benchmark_io_test() {
+        mock::reset();
+        mock::push_data(&[0x47]);
+        let mut controller = unsafe { Controller::new() };
+        let timeout = controller.benchmark_io().unwrap();
+        assert!(timeout >= BENCHMARK_SAFETY_FACTOR);
+        assert_eq!(timeout % BENCHMARK_SAFETY_FACTOR, 0);
+        controller.set_timeout(timeout);
+        assert_eq!(controller.timeout(), timeout);
+        assert_eq!(mock::writes(), [(COMMAND, 0x20)]);
+    }
+
+    struct SteppedTime {
+        cycles: u64,
+        step: u64,
+    }
+
+    impl TimeSource for SteppedTime {
+        fn elapsed_cycles(&mut self) -> u64 {
+            self.cycles += self.step;
+            self.cycles
+        }
+    }
+
+    #[test]
+    fn custom_time_source_test() {
+        mock::reset();
+        let time_source = SteppedTime {
+            cycles: 0,
+            step: 10,
+        };
+        let mut controller = unsafe { Controller::with_time_source(100, time_source) };
+        assert!(matches!(
+            controller.read_data(),
+            Err(ControllerError::Timeout)
+        ));
+        assert_eq!(mock::status_reads(), 100 / 10);
+
+        // The benchmark checks the time before and after the command, and once while waiting for
+        // the response
+        mock::push_data(&[0x47]);
+        assert_eq!(
+            controller.benchmark_io().unwrap(),
+            2 * 10 * BENCHMARK_SAFETY_FACTOR
+        );
+    }
+
+    #[test]
+    fn channel_count_test() {
+        mock::reset();
+        mock::push_data(&[0x20, 0x00]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.channel_count().unwrap(), 2);
+        assert_eq!(
+            mock::writes(),
+            [
+                (COMMAND, 0xa7),
+                (COMMAND, 0x20),
+                (COMMAND, 0xa8),
+                (COMMAND, 0x20),
+                (COMMAND, 0xa7)
+            ]
+        );
+
+        mock::reset();
+        mock::push_data(&[0x20, 0x20]);
+        assert_eq!(controller.channel_count().unwrap(), 1);
+        assert_eq!(mock::writes().last(), Some(&(COMMAND, 0xa7)));
+
+        mock::reset();
+        mock::push_data(&[0x00]);
+        assert_eq!(controller.channel_count().unwrap(), 1);
+        assert_eq!(mock::writes(), [(COMMAND, 0xa7), (COMMAND, 0x20)]);
+    }
+
+    #[test]
+    fn raw_command_test() {
+        mock::reset();
+        mock::push_data(&[0x30]);
+        let mut controller = unsafe { Controller::new() };
         unsafe {
-            self.command_register.write(command);
+            controller.write_command_raw(0xc3).unwrap();
+            assert_eq!(controller.read_data_after_command(0xa1).unwrap(), 0x30);
         }
-        Ok(())
+        assert_eq!(mock::writes(), [(COMMAND, 0xc3), (COMMAND, 0xa1)]);
+    }
+
+    #[test]
+    fn last_status_test() {
+        mock::reset();
+        mock::push_mouse_data(&[0x08]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.last_status(), None);
+        controller.read_data().unwrap();
+        assert_eq!(
+            controller.last_status(),
+            Some(ControllerStatusFlags::OUTPUT_FULL | ControllerStatusFlags::MOUSE_OUTPUT_FULL)
+        );
+        assert_eq!(mock::status_reads(), 1);
+    }
+
+    #[test]
+    fn read_status_checked_test() {
+        mock::reset();
+        mock::push_with_status(0xff, ControllerStatusFlags::PARITY_ERR);
+        let mut controller = unsafe { Controller::new() };
+        let err = controller.read_status_checked().unwrap_err();
+        assert!(err.parity_error());
+        assert!(err.status.contains(ControllerStatusFlags::OUTPUT_FULL));
+        controller.read_data().unwrap();
+        assert_eq!(
+            controller.read_status_checked().unwrap(),
+            ControllerStatusFlags::empty()
+        );
+    }
+
+    #[test]
+    fn skips_redundant_write_polls_test() {
+        mock::reset();
+        mock::push_reply(&[0xfa]);
+        mock::push_reply(&[0xfa]);
+        let mut controller = unsafe { Controller::with_timeout(10) };
+        controller.write_data(0xed).unwrap();
+        controller.read_data().unwrap();
+        // Reading the response showed the input buffer was empty, so no poll is needed here
+        controller.write_data(0x02).unwrap();
+        controller.read_data().unwrap();
+        assert_eq!(mock::status_reads(), 3);
+
+        // The second byte still waits for the first to be consumed
+        controller.send_command_and_data(0xd4, 0xf4).unwrap();
+        assert_eq!(mock::status_reads(), 4);
+
+        mock::reset();
+        mock::push_data(&[0xfa]);
+        mock::set_input_full(true);
+        controller.read_data().unwrap();
+        assert!(matches!(
+            controller.write_data(0xf4),
+            Err(ControllerError::Timeout)
+        ));
+        assert!(mock::writes().is_empty());
+    }
+
+    #[test]
+    fn wedge_threshold_test() {
+        mock::reset();
+        mock::set_input_full(true);
+        let mut controller = unsafe { Controller::with_timeout(4) };
+        controller.set_wedge_threshold(Some(2));
+        for _ in 0..2 {
+            assert!(matches!(
+                controller.write_data(0xf4),
+                Err(ControllerError::Timeout)
+            ));
+        }
+        assert!(controller.is_wedged());
+        let status_reads = mock::status_reads();
+        assert!(matches!(
+            controller.write_data(0xf4),
+            Err(ControllerError::ControllerWedged)
+        ));
+        assert_eq!(mock::status_reads(), status_reads);
+
+        mock::set_input_full(false);
+        controller.clear_wedged();
+        assert!(!controller.is_wedged());
+        controller.write_data(0xf4).unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xf4)]);
+    }
+
+    std::thread_local! {
+        static TRACE: RefCell<Vec<(IoDirection, IoPort, u8)>> = const { RefCell::new(Vec::new()) };
+    }
+
+    fn record_trace(direction: IoDirection, port: IoPort, value: u8) {
+        TRACE.with(|trace| trace.borrow_mut().push((direction, port, value)));
+    }
+
+    #[test]
+    fn trace_test() {
+        mock::reset();
+        mock::push_data(&[0x47]);
+        let mut controller = unsafe { Controller::new() };
+        controller.set_trace(Some(record_trace));
+        controller.read_config().unwrap();
+        controller.set_trace(None);
+        controller.write_data(0xf4).unwrap();
+        assert_eq!(
+            TRACE.with(|trace| trace.borrow().clone()),
+            [
+                (IoDirection::Read, IoPort::Command, 0x01),
+                (IoDirection::Write, IoPort::Command, 0x20),
+                (IoDirection::Read, IoPort::Command, 0x01),
+                (IoDirection::Read, IoPort::Data, 0x47)
+            ]
+        );
+    }
+
+    #[test]
+    fn dump_state_test() {
+        mock::reset();
+        mock::push_data(&[0x67, 0x03, 0x80]);
+        let mut controller = unsafe { Controller::new() };
+        let mut dump = std::string::String::new();
+        controller.dump_state(&mut dump).unwrap();
+        assert_eq!(
+            dump,
+            "status: OUTPUT_FULL\n\
+             config: ENABLE_KEYBOARD_INTERRUPT | ENABLE_MOUSE_INTERRUPT | SET_SYSTEM_FLAG | DISABLE_MOUSE | ENABLE_TRANSLATE\n\
+             output port: SYSTEM_RESET | A20_GATE\n\
+             input port: KEYBOARD_ENABLED\n\
+             keyboard interface: enabled in config\n\
+             mouse interface: disabled in config\n"
+        );
+        assert_eq!(
+            mock::writes(),
+            [(COMMAND, 0x20), (COMMAND, 0xd0), (COMMAND, 0xc0)]
+        );
+    }
+
+    #[test]
+    fn parity_retry_test() {
+        mock::reset();
+        mock::push_with_status(0xff, ControllerStatusFlags::PARITY_ERR);
+        mock::push_data(&[0x1c]);
+        let mut controller = unsafe { Controller::new() };
+        controller.set_parity_retries(1);
+        assert_eq!(controller.read_data().unwrap(), 0x1c);
+
+        mock::reset();
+        mock::push_with_status(0xff, ControllerStatusFlags::PARITY_ERR);
+        mock::push_with_status(0xff, ControllerStatusFlags::PARITY_ERR);
+        assert!(matches!(
+            controller.read_data(),
+            Err(ControllerError::ParityError)
+        ));
+
+        mock::reset();
+        mock::push_with_status(0xff, ControllerStatusFlags::PARITY_ERR);
+        controller.set_parity_retries(0);
+        assert_eq!(controller.read_data().unwrap(), 0xff);
+    }
+
+    #[test]
+    fn pulse_output_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        controller.pulse_output_low_nibble(0x0f).unwrap();
+        controller.pulse_output_low_nibble(0x01).unwrap();
+        controller.pulse_output_low_nibble(0x00).unwrap();
+        controller.pulse_output(OutputPortFlags::empty()).unwrap();
+        controller.pulse_output(OutputPortFlags::A20_GATE).unwrap();
+        controller.pulse_reset_line().unwrap();
+        assert_eq!(
+            mock::writes(),
+            [
+                (COMMAND, 0xff),
+                (COMMAND, 0xf1),
+                (COMMAND, 0xf0),
+                (COMMAND, 0xff),
+                (COMMAND, 0xfd),
+                (COMMAND, 0xfe)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_controller_with_retry_test() {
+        mock::reset();
+        mock::push_data(&[0xfc, 0x00, 0x55]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.test_controller_with_retry(3).unwrap(), 3);
+
+        mock::reset();
+        mock::push_data(&[0xfc, 0x55]);
+        assert!(matches!(
+            controller.test_controller_with_retry(1),
+            Err(ControllerError::TestFailed { response: 0xfc })
+        ));
+        assert_eq!(mock::pending(), 1);
+
+        mock::reset();
+        mock::push_data(&[0x55]);
+        assert_eq!(controller.test_controller_with_retry(0).unwrap(), 1);
+    }
+
+    #[test]
+    fn strict_mode_test() {
+        mock::reset();
+        mock::push_mouse_data(&[0xfa]);
+        mock::push_data(&[0x55]);
+        let mut controller = unsafe { Controller::new() };
+        controller.set_strict(true);
+        assert!(matches!(
+            controller.test_controller(),
+            Err(ControllerError::UnexpectedMouseData(0xfa))
+        ));
+
+        mock::reset();
+        mock::push_mouse_data(&[0x08]);
+        assert!(matches!(
+            controller.read_internal_ram(0),
+            Err(ControllerError::UnexpectedMouseData(0x08))
+        ));
+
+        mock::reset();
+        mock::push_mouse_data(&[0x55]);
+        controller.set_strict(false);
+        controller.test_controller().unwrap();
+    }
+
+    #[test]
+    fn initialize_test() {
+        mock::reset();
+        mock::emulate_controller(0x47);
+        mock::push_data(&[0x1c, 0xf0]);
+        let mut controller = unsafe { Controller::new() };
+        let result = controller.initialize().unwrap();
+        assert_eq!(
+            result,
+            InitResult {
+                keyboard: true,
+                mouse: true
+            }
+        );
+        assert_eq!(mock::config(), 0x04);
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn with_devices_quiesced_test() {
+        mock::reset();
+        mock::emulate_controller(0x07);
+        mock::push_data(&[0x1c]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .with_devices_quiesced(|controller| {
+                assert_eq!(mock::config(), 0x37);
+                mock::push_data(&[0xf0]);
+                controller.test_controller()
+            })
+            .unwrap_err();
+        assert_eq!(mock::config(), 0x07);
+
+        mock::push_data(&[0x1c]);
+        controller
+            .with_devices_quiesced(Controller::test_controller)
+            .unwrap();
+        assert_eq!(mock::config(), 0x07);
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn initiate_system_reset_test() {
+        mock::reset();
+        mock::set_input_full(true);
+        let mut controller = unsafe { Controller::with_timeout(4) };
+        assert!(matches!(
+            controller.initiate_system_reset(10),
+            Err(ControllerError::Timeout)
+        ));
+        assert!(mock::writes().is_empty());
+    }
+
+    #[test]
+    fn interrupt_lines_test() {
+        mock::reset();
+        mock::push_data(&[0x13, 0x23, 0x03]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.interrupt_lines().unwrap(), (true, false));
+        assert_eq!(controller.interrupt_lines().unwrap(), (false, true));
+        assert_eq!(controller.interrupt_lines().unwrap(), (false, false));
+        assert_eq!(mock::writes(), [(COMMAND, 0xd0); 3]);
+    }
+
+    #[test]
+    fn configure_a20_test() {
+        mock::reset();
+        mock::push_data(&[0x01, 0x03]);
+        let mut controller = unsafe { Controller::new() };
+        controller.configure_a20(true).unwrap();
+        assert_eq!(
+            mock::writes(),
+            [
+                (COMMAND, 0xd0),
+                (COMMAND, 0xd1),
+                (DATA, 0x03),
+                (COMMAND, 0xd0)
+            ]
+        );
+
+        mock::reset();
+        mock::push_data(&[0x01, 0x01]);
+        assert!(matches!(
+            controller.configure_a20(true),
+            Err(ControllerError::A20VerificationFailed)
+        ));
+
+        mock::reset();
+        mock::push_data(&[0x03]);
+        assert!(controller.a20_enabled().unwrap());
+    }
+
+    #[test]
+    fn write_output_port_safe_test() {
+        mock::reset();
+        mock::push_data(&[0x03, 0x03, 0x03]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .write_output_port_safe(|output| output | OutputPortFlags::A20_GATE, false)
+            .unwrap();
+        controller
+            .write_output_port_safe(|output| output - OutputPortFlags::A20_GATE, false)
+            .unwrap();
+        assert!(matches!(
+            controller.write_output_port_safe(|_| OutputPortFlags::A20_GATE, false),
+            Err(ControllerError::ResetNotAllowed)
+        ));
+        assert_eq!(
+            mock::writes(),
+            [
+                (COMMAND, 0xd0),
+                (COMMAND, 0xd0),
+                (COMMAND, 0xd1),
+                (DATA, 0x01),
+                (COMMAND, 0xd0)
+            ]
+        );
+    }
+
+    #[test]
+    fn service_interrupt_test() {
+        mock::reset();
+        mock::push_data(&[0x1c]);
+        mock::push_mouse_data(&[0x08]);
+        mock::push_data(&[0xf0]);
+        let mut controller = unsafe { Controller::new() };
+        let mut received = Vec::new();
+        while let Some(byte) = controller.service_interrupt() {
+            received.push(byte);
+        }
+        assert_eq!(
+            received,
+            [
+                (DataSource::Keyboard, 0x1c),
+                (DataSource::Mouse, 0x08),
+                (DataSource::Keyboard, 0xf0)
+            ]
+        );
+        assert_eq!(controller.service_interrupt(), None);
+    }
+
+    #[test]
+    fn for_each_pending_byte_test() {
+        mock::reset();
+        mock::push_mouse_data(&[0x08, 0x01, 0x02]);
+        mock::push_data(&[0x1c]);
+        let mut controller = unsafe { Controller::new() };
+        let mut mouse_bytes = 0;
+        let mut keyboard_bytes = Vec::new();
+        let count = controller.for_each_pending_byte(|byte, source| match source {
+            DataSource::Keyboard => keyboard_bytes.push(byte),
+            DataSource::Mouse => mouse_bytes += 1,
+        });
+        assert_eq!((count, mouse_bytes), (4, 3));
+        assert_eq!(keyboard_bytes, [0x1c]);
+        assert_eq!(controller.for_each_pending_byte(|_, _| {}), 0);
+    }
+
+    #[test]
+    fn scancode_diagnostics_test() {
+        mock::reset();
+        // Config byte with translation enabled, then the translated response for set 2
+        mock::push_data(&[0x45, 0xfa, 0xfa, 0x41]);
+        let mut controller = unsafe { Controller::new() };
+        let diagnostics = controller.scancode_diagnostics().unwrap();
+        assert_eq!(
+            diagnostics,
+            ScancodeDiagnostics {
+                translation_enabled: true,
+                keyboard_set: 2
+            }
+        );
+        assert!(diagnostics.is_mismatched());
+    }
+
+    #[test]
+    fn device_markers_test() {
+        mock::reset();
+        mock::push_data(&[0xfa]);
+        let mut controller = unsafe { Controller::new() }.without_mouse();
+        controller.keyboard().set_defaults().unwrap();
+        let mut controller = controller.without_keyboard();
+        controller.disable_keyboard().unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xf6), (COMMAND, 0xad)]);
     }
 }
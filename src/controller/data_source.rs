@@ -0,0 +1,6 @@
+/// The device that placed a byte in the controller's data buffer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DataSource {
+    Keyboard,
+    Mouse,
+}
@@ -0,0 +1,9 @@
+/// The devices found by [`Controller::initialize`](super::Controller::initialize).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InitResult {
+    /// Whether the keyboard interface passed its self-test and was enabled.
+    pub keyboard: bool,
+    /// Whether the controller has a second channel, and the mouse interface passed its self-test
+    /// and was enabled.
+    pub mouse: bool,
+}
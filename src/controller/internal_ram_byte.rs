@@ -0,0 +1,68 @@
+/// A byte of the controller's internal RAM, with names for the bytes whose meaning is known.
+///
+/// Apart from [`InternalRamByte::Config`], the meanings of these bytes come from AMI controllers,
+/// and other controllers may use them for something else entirely.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InternalRamByte {
+    /// Byte `0x00`, the configuration byte. See
+    /// [`ControllerConfigFlags`](crate::flags::ControllerConfigFlags).
+    Config,
+    /// Byte `0x13`, which is nonzero when password security is enabled. AMI controllers only.
+    SecurityEnabled,
+    /// Byte `0x14`, which is nonzero when a password match enables the keyboard. AMI controllers
+    /// only.
+    PasswordEnablesKeyboard,
+    /// Byte `0x16`, the first of two make codes discarded while matching a password. AMI
+    /// controllers only.
+    PasswordDiscardFirst,
+    /// Byte `0x17`, the second of two make codes discarded while matching a password. AMI
+    /// controllers only.
+    PasswordDiscardSecond,
+    /// Any other byte, by index. Only the low 5 bits of the index are used.
+    Other(u8),
+}
+
+impl InternalRamByte {
+    /// The index of this byte in the controller's internal RAM.
+    pub const fn index(&self) -> u8 {
+        match self {
+            InternalRamByte::Config => 0x00,
+            InternalRamByte::SecurityEnabled => 0x13,
+            InternalRamByte::PasswordEnablesKeyboard => 0x14,
+            InternalRamByte::PasswordDiscardFirst => 0x16,
+            InternalRamByte::PasswordDiscardSecond => 0x17,
+            InternalRamByte::Other(index) => *index & 0x1f,
+        }
+    }
+}
+
+impl From<u8> for InternalRamByte {
+    fn from(index: u8) -> Self {
+        match index & 0x1f {
+            0x00 => InternalRamByte::Config,
+            0x13 => InternalRamByte::SecurityEnabled,
+            0x14 => InternalRamByte::PasswordEnablesKeyboard,
+            0x16 => InternalRamByte::PasswordDiscardFirst,
+            0x17 => InternalRamByte::PasswordDiscardSecond,
+            other => InternalRamByte::Other(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn index_test() {
+        assert_eq!(InternalRamByte::Config.index(), 0x00);
+        assert_eq!(InternalRamByte::SecurityEnabled.index(), 0x13);
+        assert_eq!(InternalRamByte::PasswordEnablesKeyboard.index(), 0x14);
+        assert_eq!(InternalRamByte::PasswordDiscardFirst.index(), 0x16);
+        assert_eq!(InternalRamByte::PasswordDiscardSecond.index(), 0x17);
+        assert_eq!(InternalRamByte::Other(0x25).index(), 0x05);
+        for index in 0..32 {
+            assert_eq!(InternalRamByte::from(index).index(), index);
+        }
+    }
+}
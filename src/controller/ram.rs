@@ -0,0 +1,76 @@
+use core::fmt;
+
+use crate::flags::ControllerConfigFlags;
+
+/// The contents of the controller's internal RAM, as returned by
+/// [`Controller::diagnostic_dump`](crate::Controller::diagnostic_dump).
+///
+/// Byte 0 is the configuration byte, and the meaning of the other bytes is specific to the
+/// controller. The `Debug` output shows the parsed configuration byte followed by the other bytes.
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub struct ControllerRam {
+    bytes: [u8; 32],
+}
+
+impl ControllerRam {
+    /// Wrap the raw bytes of the controller's internal RAM.
+    pub const fn new(bytes: [u8; 32]) -> Self {
+        Self { bytes }
+    }
+
+    /// The configuration byte, stored in byte 0.
+    pub const fn config(&self) -> ControllerConfigFlags {
+        ControllerConfigFlags::from_bits_truncate(self.bytes[0])
+    }
+
+    /// The byte at the given index, or `None` if the index is not between 0 and 31.
+    pub fn byte(&self, index: usize) -> Option<u8> {
+        self.bytes.get(index).copied()
+    }
+
+    /// The raw bytes of the internal RAM.
+    pub const fn bytes(&self) -> &[u8; 32] {
+        &self.bytes
+    }
+}
+
+impl From<[u8; 32]> for ControllerRam {
+    fn from(bytes: [u8; 32]) -> Self {
+        Self::new(bytes)
+    }
+}
+
+impl fmt::Debug for ControllerRam {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ControllerRam")
+            .field("config", &self.config())
+            .field("bytes", &&self.bytes[1..])
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+
+    #[test]
+    fn parses_config_test() {
+        let mut bytes = [0; 32];
+        bytes[0] = 0x45;
+        bytes[31] = 0xaa;
+        let ram = ControllerRam::from(bytes);
+        assert_eq!(
+            ram.config(),
+            ControllerConfigFlags::ENABLE_KEYBOARD_INTERRUPT
+                | ControllerConfigFlags::SET_SYSTEM_FLAG
+                | ControllerConfigFlags::ENABLE_TRANSLATE
+        );
+        assert_eq!(ram.byte(31), Some(0xaa));
+        assert_eq!(ram.byte(32), None);
+        assert_eq!(ram.bytes(), &bytes);
+        assert!(std::format!("{:?}", ram)
+            .starts_with("ControllerRam { config: ENABLE_KEYBOARD_INTERRUPT | SET_SYSTEM_FLAG"));
+    }
+}
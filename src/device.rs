@@ -0,0 +1,89 @@
+use crate::{
+    error::{KeyboardError, MouseError},
+    keyboard::{Keyboard, KeyboardType},
+    mouse::{Mouse, MouseType},
+    time_source::TimeSource,
+};
+
+/// Commands shared by all PS/2 devices, allowing generic code to manage the keyboard and mouse
+/// uniformly. Device-specific commands are available as inherent methods of [`Keyboard`] and
+/// [`Mouse`].
+pub trait Ps2Device {
+    /// The error returned by the device's commands.
+    type Error;
+    /// The identifier reported by the device.
+    type DeviceType;
+
+    /// Reset the device and perform a Basic Assurance Test.
+    fn reset_and_self_test(&mut self) -> Result<(), Self::Error>;
+
+    /// Restore the device's default settings.
+    fn set_defaults(&mut self) -> Result<(), Self::Error>;
+
+    /// Attempt to obtain the device identifier.
+    fn device_type(&mut self) -> Result<Self::DeviceType, Self::Error>;
+}
+
+impl<T: TimeSource, M> Ps2Device for Keyboard<'_, T, M> {
+    type Error = KeyboardError;
+    type DeviceType = KeyboardType;
+
+    fn reset_and_self_test(&mut self) -> Result<(), Self::Error> {
+        Keyboard::reset_and_self_test(self)
+    }
+
+    fn set_defaults(&mut self) -> Result<(), Self::Error> {
+        Keyboard::set_defaults(self)
+    }
+
+    fn device_type(&mut self) -> Result<Self::DeviceType, Self::Error> {
+        self.get_keyboard_type()
+    }
+}
+
+impl<T: TimeSource, K> Ps2Device for Mouse<'_, T, K> {
+    type Error = MouseError;
+    type DeviceType = MouseType;
+
+    fn reset_and_self_test(&mut self) -> Result<(), Self::Error> {
+        Mouse::reset_and_self_test(self).map(|_| ())
+    }
+
+    fn set_defaults(&mut self) -> Result<(), Self::Error> {
+        Mouse::set_defaults(self)
+    }
+
+    fn device_type(&mut self) -> Result<Self::DeviceType, Self::Error> {
+        self.get_mouse_type()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        controller::Controller,
+        mock::{self, COMMAND, DATA},
+        COMMAND_ACKNOWLEDGED,
+    };
+
+    fn restore_defaults<D: Ps2Device + ?Sized>(device: &mut D) -> Result<(), D::Error> {
+        device.set_defaults()
+    }
+
+    #[test]
+    fn generic_dispatch_test() {
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        restore_defaults(&mut controller.keyboard()).unwrap();
+        let mouse: &mut dyn Ps2Device<Error = MouseError, DeviceType = MouseType> =
+            &mut controller.mouse();
+        restore_defaults(mouse).unwrap();
+        assert_eq!(
+            mock::writes(),
+            [(DATA, 0xf6), (COMMAND, 0xd4), (DATA, 0xf6)]
+        );
+    }
+}
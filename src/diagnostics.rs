@@ -0,0 +1,53 @@
+/// The scancode set used by the keyboard, compared with the one software actually receives.
+///
+/// When [`ControllerConfigFlags::ENABLE_TRANSLATE`](crate::flags::ControllerConfigFlags) is set,
+/// the controller translates everything the keyboard sends into scancode set 1. A keyboard in set
+/// 2 with translation enabled therefore produces set 1 scancodes, which is a common source of
+/// confusion.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScancodeDiagnostics {
+    /// Whether the controller translates scancodes to set 1.
+    pub translation_enabled: bool,
+    /// The scancode set reported by the keyboard.
+    pub keyboard_set: u8,
+}
+
+impl ScancodeDiagnostics {
+    /// The scancode set that software reading the data buffer will receive.
+    pub const fn effective_set(&self) -> u8 {
+        if self.translation_enabled {
+            1
+        } else {
+            self.keyboard_set
+        }
+    }
+
+    /// Whether the effective scancode set differs from the keyboard's scancode set.
+    pub const fn is_mismatched(&self) -> bool {
+        self.effective_set() != self.keyboard_set
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scancode_mismatch_test() {
+        for &(translation_enabled, keyboard_set, effective_set, mismatched) in &[
+            (false, 1, 1, false),
+            (false, 2, 2, false),
+            (false, 3, 3, false),
+            (true, 1, 1, false),
+            (true, 2, 1, true),
+            (true, 3, 1, true),
+        ] {
+            let diagnostics = ScancodeDiagnostics {
+                translation_enabled,
+                keyboard_set,
+            };
+            assert_eq!(diagnostics.effective_set(), effective_set);
+            assert_eq!(diagnostics.is_mismatched(), mismatched);
+        }
+    }
+}
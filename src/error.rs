@@ -1,37 +1,138 @@
+use crate::flags::ControllerStatusFlags;
+
 #[derive(Debug)]
 pub enum ControllerError {
     Timeout,
+    DeviceTimeout,
+    ParityError,
+    ResetNotAllowed,
     TestFailed { response: u8 },
+    UnmappedCharacter(char),
+    UnsupportedScancodeSet(u8),
+    UnexpectedMouseData(u8),
+    A20VerificationFailed,
+    ControllerWedged,
 }
 
 #[derive(Debug)]
 pub enum KeyboardError {
+    Timeout,
     BufferOverrun,
-    SelfTestFailed,
+    SelfTestFailed { response: u8 },
+    SelfTestTimeout,
     Resend,
     KeyDetectionError,
     InvalidResponse(u8),
+    UnexpectedAck { command: u8 },
+    InvalidTypematicFrequency(u8),
+    InvalidTypematicDelay(u8),
     ControllerError(ControllerError),
 }
 
 #[derive(Debug)]
 pub enum MouseError {
-    SelfTestFailed,
+    Timeout,
+    SelfTestFailed { response: u8 },
+    SelfTestTimeout,
     Resend,
     InvalidResponse(u8),
     InvalidResolution(u8),
     InvalidSampleRate(u8),
+    InvalidStatusFlags(u8),
+    UnexpectedDeviceId(u8),
     ControllerError(ControllerError),
 }
 
+/// A status register value with one or both of the error bits set. See
+/// [`ControllerStatusFlags::parse`].
+#[derive(Debug)]
+pub struct StatusError {
+    /// The full status that was read.
+    pub status: ControllerStatusFlags,
+}
+
+impl StatusError {
+    /// Whether [`ControllerStatusFlags::PARITY_ERR`] is set.
+    pub const fn parity_error(&self) -> bool {
+        self.status.contains(ControllerStatusFlags::PARITY_ERR)
+    }
+
+    /// Whether [`ControllerStatusFlags::TIMEOUT_ERR`] is set.
+    pub const fn timeout_error(&self) -> bool {
+        self.status.contains(ControllerStatusFlags::TIMEOUT_ERR)
+    }
+}
+
+/// Converts to [`ControllerError::ParityError`] if the parity bit is set, and otherwise to
+/// [`ControllerError::DeviceTimeout`], since the timeout bit reports a timeout detected by the
+/// controller rather than one of this crate's own IO timeouts.
+impl From<StatusError> for ControllerError {
+    fn from(err: StatusError) -> Self {
+        if err.parity_error() {
+            ControllerError::ParityError
+        } else {
+            ControllerError::DeviceTimeout
+        }
+    }
+}
+
 impl From<ControllerError> for KeyboardError {
     fn from(err: ControllerError) -> Self {
-        KeyboardError::ControllerError(err)
+        match err {
+            ControllerError::Timeout => KeyboardError::Timeout,
+            other => KeyboardError::ControllerError(other),
+        }
     }
 }
 
 impl From<ControllerError> for MouseError {
     fn from(err: ControllerError) -> Self {
-        MouseError::ControllerError(err)
+        match err {
+            ControllerError::Timeout => MouseError::Timeout,
+            other => MouseError::ControllerError(other),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timeout_conversion_test() {
+        assert!(matches!(
+            KeyboardError::from(ControllerError::Timeout),
+            KeyboardError::Timeout
+        ));
+        assert!(matches!(
+            MouseError::from(ControllerError::Timeout),
+            MouseError::Timeout
+        ));
+        assert!(matches!(
+            MouseError::from(ControllerError::ParityError),
+            MouseError::ControllerError(ControllerError::ParityError)
+        ));
+    }
+
+    #[test]
+    fn status_error_conversion_test() {
+        let err = StatusError {
+            status: ControllerStatusFlags::PARITY_ERR | ControllerStatusFlags::TIMEOUT_ERR,
+        };
+        assert!(err.parity_error() && err.timeout_error());
+        assert!(matches!(
+            ControllerError::from(err),
+            ControllerError::ParityError
+        ));
+        assert!(matches!(
+            ControllerError::from(StatusError {
+                status: ControllerStatusFlags::TIMEOUT_ERR,
+            }),
+            ControllerError::DeviceTimeout
+        ));
+        assert!(matches!(
+            KeyboardError::from(ControllerError::DeviceTimeout),
+            KeyboardError::ControllerError(ControllerError::DeviceTimeout)
+        ));
     }
 }
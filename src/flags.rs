@@ -1,5 +1,7 @@
 use bitflags::bitflags;
 
+use crate::{error::StatusError, mouse::MouseMode};
+
 bitflags! {
     pub struct ControllerStatusFlags: u8 {
         /// Whether there is data available to read at port `0x60`.
@@ -23,6 +25,19 @@ bitflags! {
     }
 }
 
+impl ControllerStatusFlags {
+    /// Create status flags from a raw status byte, returning a [`StatusError`] if
+    /// [`ControllerStatusFlags::PARITY_ERR`] or [`ControllerStatusFlags::TIMEOUT_ERR`] is set.
+    pub const fn parse(bits: u8) -> Result<Self, StatusError> {
+        let status = Self::from_bits_truncate(bits);
+        if status.intersects(Self::PARITY_ERR.union(Self::TIMEOUT_ERR)) {
+            Err(StatusError { status })
+        } else {
+            Ok(status)
+        }
+    }
+}
+
 bitflags! {
     pub struct ControllerConfigFlags: u8 {
         /// Whether the keyboard should trigger any interrupts.
@@ -41,6 +56,22 @@ bitflags! {
     }
 }
 
+impl ControllerConfigFlags {
+    /// Bits 3 and 7 of the configuration byte, which are reserved. Bit 3 must be zero, and bit 7
+    /// should be zero, according to most references. Some controllers misbehave if a configuration
+    /// byte with either bit set is written.
+    const RESERVED_BITS: u8 = 0b10001000;
+
+    /// Create configuration flags from a raw byte, explicitly clearing the reserved bits.
+    ///
+    /// This has the same result as [`ControllerConfigFlags::from_bits_truncate`], but makes it
+    /// clear at the call site that the reserved bits are being discarded on purpose, for example
+    /// when a byte read from the controller is modified and written back.
+    pub const fn new_masked(bits: u8) -> Self {
+        Self::from_bits_truncate(bits & !Self::RESERVED_BITS)
+    }
+}
+
 bitflags! {
     pub struct InputPortFlags: u8 {
         /// Keyboard input data line.
@@ -60,7 +91,8 @@ bitflags! {
 
 bitflags! {
     pub struct OutputPortFlags: u8 {
-        /// Whether to reset the CPU.
+        /// The CPU reset line, which is active low. This should always be set, since clearing it
+        /// resets the CPU.
         const SYSTEM_RESET       = 0b00000001;
         /// Whether the 20th address line is enabled.
         const A20_GATE           = 0b00000010;
@@ -105,11 +137,36 @@ bitflags! {
     }
 }
 
+impl MouseStatusFlags {
+    /// Bits 3 and 7 of the status byte, which are always zero according to the specification.
+    const RESERVED_BITS: u8 = 0b10001000;
+
+    /// The operating mode reported in the status byte, either [`MouseMode::Remote`] or
+    /// [`MouseMode::Stream`].
+    ///
+    /// A mouse in wrap mode echoes the status request instead of replying to it, so the status
+    /// byte can never indicate [`MouseMode::Wrap`].
+    pub const fn operating_mode(&self) -> MouseMode {
+        if self.contains(Self::REMOTE_MODE_ENABLED) {
+            MouseMode::Remote
+        } else {
+            MouseMode::Stream
+        }
+    }
+
+    /// Whether a raw status byte has its reserved bits clear.
+    pub(crate) const fn is_valid_byte(byte: u8) -> bool {
+        byte & Self::RESERVED_BITS == 0
+    }
+}
+
 bitflags! {
     pub struct MouseMovementFlags: u8 {
         const LEFT_BUTTON_PRESSED   = 0b00000001;
         const RIGHT_BUTTON_PRESSED  = 0b00000010;
         const MIDDLE_BUTTON_PRESSED = 0b00000100;
+        /// Always set in a valid packet. Useful for detecting the start of a packet.
+        const ALWAYS_ONE            = 0b00001000;
         const X_SIGN_BIT            = 0b00010000;
         const Y_SIGN_BIT            = 0b00100000;
         const X_OVERFLOW            = 0b01000000;
@@ -117,6 +174,23 @@ bitflags! {
     }
 }
 
+impl MouseMovementFlags {
+    pub(crate) const BUTTONS: Self = Self::LEFT_BUTTON_PRESSED
+        .union(Self::RIGHT_BUTTON_PRESSED)
+        .union(Self::MIDDLE_BUTTON_PRESSED);
+
+    /// The state of the buttons as a bitmask, with the left button in bit 0, the right button in
+    /// bit 1, and the middle button in bit 2.
+    pub const fn button_state(&self) -> u8 {
+        self.bits() & Self::BUTTONS.bits()
+    }
+
+    /// The buttons that were pressed or released since the `previous` packet.
+    pub const fn changed_buttons(&self, previous: MouseMovementFlags) -> MouseMovementFlags {
+        Self::from_bits_truncate(self.button_state() ^ previous.button_state())
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -128,6 +202,29 @@ mod tests {
         assert_eq!(InputPortFlags::all().bits(), 0b11110011);
     }
 
+    #[test]
+    fn status_parse_test() {
+        assert_eq!(
+            ControllerStatusFlags::parse(0x21).unwrap(),
+            ControllerStatusFlags::OUTPUT_FULL | ControllerStatusFlags::MOUSE_OUTPUT_FULL
+        );
+        let err = ControllerStatusFlags::parse(0x81).unwrap_err();
+        assert!(err.parity_error() && !err.timeout_error());
+        assert!(ControllerStatusFlags::parse(0x40)
+            .unwrap_err()
+            .timeout_error());
+    }
+
+    #[test]
+    fn config_reserved_bits_test() {
+        assert_eq!(ControllerConfigFlags::new_masked(0xff).bits(), 0b01110111);
+        assert_eq!(ControllerConfigFlags::new_masked(0b00001000).bits(), 0);
+        assert_eq!(
+            ControllerConfigFlags::new_masked(0x47),
+            ControllerConfigFlags::from_bits_truncate(0x47)
+        );
+    }
+
     #[test]
     fn handles_all_zeroes_test() {
         assert_eq!(
@@ -139,4 +236,56 @@ mod tests {
             (0, 0, 0)
         );
     }
+
+    #[test]
+    fn const_construction_test() {
+        const CONFIG: ControllerConfigFlags = ControllerConfigFlags::ENABLE_KEYBOARD_INTERRUPT
+            .union(ControllerConfigFlags::ENABLE_MOUSE_INTERRUPT)
+            .union(ControllerConfigFlags::SET_SYSTEM_FLAG);
+        const OUTPUT: OutputPortFlags =
+            OutputPortFlags::from_bits_truncate(0xff).difference(OutputPortFlags::A20_GATE);
+        const LEDS: KeyboardLedFlags = KeyboardLedFlags::empty().union(KeyboardLedFlags::NUM_LOCK);
+
+        assert_eq!(CONFIG.bits(), 0b00000111);
+        assert_eq!(OUTPUT.bits(), 0b11111101);
+        assert_eq!(LEDS.bits(), 0b010);
+    }
+
+    #[test]
+    fn mouse_status_reserved_bits_test() {
+        assert!(MouseStatusFlags::is_valid_byte(
+            MouseStatusFlags::all().bits()
+        ));
+        assert!(!MouseStatusFlags::is_valid_byte(0x80));
+        assert!(MouseStatusFlags::is_valid_byte(0x77));
+    }
+
+    #[test]
+    fn mouse_operating_mode_test() {
+        for &(byte, mode) in &[
+            (0x00, MouseMode::Stream),
+            (0x20, MouseMode::Stream),
+            (0x40, MouseMode::Remote),
+            (0x67, MouseMode::Remote),
+        ] {
+            assert_eq!(
+                MouseStatusFlags::from_bits_truncate(byte).operating_mode(),
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn button_state_test() {
+        let previous = MouseMovementFlags::ALWAYS_ONE | MouseMovementFlags::LEFT_BUTTON_PRESSED;
+        let current = MouseMovementFlags::ALWAYS_ONE
+            | MouseMovementFlags::X_SIGN_BIT
+            | MouseMovementFlags::MIDDLE_BUTTON_PRESSED;
+        assert_eq!(previous.button_state(), 0b001);
+        assert_eq!(current.button_state(), 0b100);
+        assert_eq!(
+            current.changed_buttons(previous),
+            MouseMovementFlags::LEFT_BUTTON_PRESSED | MouseMovementFlags::MIDDLE_BUTTON_PRESSED
+        );
+    }
 }
@@ -0,0 +1,30 @@
+/// The direction of a transfer between the CPU and the controller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoDirection {
+    /// A byte read by the CPU.
+    Read,
+    /// A byte written by the CPU.
+    Write,
+}
+
+/// One of the controller's IO ports.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IoPort {
+    /// The data port, `0x60` by default.
+    Data,
+    /// The port for the command register when written and the status register when read, `0x64`
+    /// by default.
+    Command,
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn root_export_test() {
+        let transfer = (crate::IoDirection::Write, crate::IoPort::Command);
+        let copy = transfer;
+        assert_eq!(transfer, copy);
+        assert_ne!(crate::IoDirection::Read, crate::IoDirection::Write);
+        assert_ne!(crate::IoPort::Data, crate::IoPort::Command);
+    }
+}
@@ -1,20 +1,52 @@
 use crate::{
-    controller::Controller,
+    controller::{Controller, DataSource},
     error::{ControllerError, KeyboardError},
-    flags::KeyboardLedFlags,
-    COMMAND_ACKNOWLEDGED, RESEND, SELF_TEST_FAILED, SELF_TEST_PASSED,
+    flags::{ControllerStatusFlags, KeyboardLedFlags},
+    presence::{HasKeyboard, HasMouse},
+    scancodes::{set1, set2::BREAK_PREFIX, EXTENDED_PREFIX},
+    time_source::{CycleCounter, TimeSource},
+    COMMAND_ACKNOWLEDGED, RESEND, SELF_TEST_PASSED,
 };
 
-pub use self::keyboard_type::KeyboardType;
+pub use self::{
+    key_behavior::KeyBehavior,
+    key_event::{KeyEvent, KeyEventDecoder, ScancodeSequence},
+    keyboard_type::KeyboardType,
+    scancode_set::ScancodeSet,
+    typematic_config::TypematicConfig,
+};
 
+pub(crate) mod ascii;
+mod key_behavior;
+mod key_event;
 mod keyboard_type;
+mod scancode_set;
+mod typematic_config;
 
 const BUFFER_OVERRUN: u8 = 0x00;
 const ECHO: u8 = 0xee;
 const KEY_DETECTION_ERROR: u8 = 0xff;
 
+const ECHO_ATTEMPTS: usize = 3;
+const ECHO_RETRY_DELAY: usize = 1_000;
+const MAX_EXTRA_RESET_ACKS: usize = 2;
+
 type Result<T> = core::result::Result<T, KeyboardError>;
 
+/// Undo the controller's scancode translation of a scancode set number sent by the keyboard.
+///
+/// With translation enabled, the keyboard's response to a scancode set query is translated like
+/// any other byte, so sets 1, 2, and 3 are received as `0x43`, `0x41`, and `0x3f`.
+pub(crate) const fn untranslate_scancode_set(response: u8) -> u8 {
+    match response {
+        0x43 => 1,
+        0x41 => 2,
+        0x3f => 3,
+        other => other,
+    }
+}
+
+#[derive(Clone, Copy)]
 #[repr(u8)]
 enum Command {
     SetLeds = 0xed,
@@ -50,19 +82,19 @@ enum Command {
 /// let mut keyboard = controller.keyboard();
 /// ```
 #[derive(Debug)]
-pub struct Keyboard<'c> {
-    controller: &'c mut Controller,
+pub struct Keyboard<'c, T = CycleCounter, M = HasMouse> {
+    controller: &'c mut Controller<T, HasKeyboard, M>,
 }
 
-impl<'c> Keyboard<'c> {
-    pub(crate) const fn new(controller: &'c mut Controller) -> Self {
+impl<'c, T: TimeSource, M> Keyboard<'c, T, M> {
+    pub(crate) const fn new(controller: &'c mut Controller<T, HasKeyboard, M>) -> Self {
         Self { controller }
     }
 
     fn check_response(&mut self) -> Result<()> {
         match self.controller.read_data()? {
+            ack if self.controller.is_ack(ack) => Ok(()),
             BUFFER_OVERRUN => Err(KeyboardError::KeyDetectionError),
-            COMMAND_ACKNOWLEDGED => Ok(()),
             RESEND => Err(KeyboardError::Resend),
             KEY_DETECTION_ERROR => Err(KeyboardError::KeyDetectionError),
             other => Err(KeyboardError::InvalidResponse(other)),
@@ -94,6 +126,24 @@ impl<'c> Keyboard<'c> {
         }
     }
 
+    /// Check that the keyboard is responding by running the echo command, retrying up to 3 times
+    /// with a short delay between attempts.
+    ///
+    /// Returns [`KeyboardError::Resend`] if none of the attempts succeed.
+    pub fn check_echo_roundtrip(&mut self) -> Result<()> {
+        for attempt in 0..ECHO_ATTEMPTS {
+            if attempt > 0 {
+                for _ in 0..ECHO_RETRY_DELAY {
+                    core::hint::spin_loop();
+                }
+            }
+            if self.echo().is_ok() {
+                return Ok(());
+            }
+        }
+        Err(KeyboardError::Resend)
+    }
+
     /// Get the number corresponding to the current scancode set (1, 2, or 3).
     pub fn get_scancode_set(&mut self) -> Result<u8> {
         self.write_command(Command::GetOrSetScancode, Some(0))?;
@@ -106,7 +156,60 @@ impl<'c> Keyboard<'c> {
         Ok(())
     }
 
+    /// Switch to a new scancode set, returning the set that was in use before.
+    ///
+    /// Responses translated by the controller are recognized. Some keyboards report a value that
+    /// isn't a valid scancode set, in which case the previous set is assumed to be
+    /// [`ScancodeSet::Set2`], the default for AT keyboards.
+    pub fn get_and_set_scancode_set(&mut self, new_set: ScancodeSet) -> Result<ScancodeSet> {
+        let previous = match untranslate_scancode_set(self.get_scancode_set()?) {
+            1 => ScancodeSet::Set1,
+            3 => ScancodeSet::Set3,
+            _ => ScancodeSet::Set2,
+        };
+        self.set_scancode_set(new_set as u8)?;
+        Ok(previous)
+    }
+
+    /// Check which scancode sets the keyboard supports, by switching to each one and reading the
+    /// current set back. Index 0 of the result is set 1, and so on.
+    ///
+    /// Responses translated by the controller are recognized, but a keyboard that rejects a set or
+    /// reports a different set afterwards is considered not to support it. The original scancode
+    /// set is restored afterwards, even if probing fails. Like in
+    /// [`Keyboard::get_and_set_scancode_set`], if the keyboard reports an invalid original set,
+    /// [`ScancodeSet::Set2`] is restored.
+    pub fn test_scancode_set_support(&mut self) -> Result<[bool; 3]> {
+        let original = match untranslate_scancode_set(self.get_scancode_set()?) {
+            1 => ScancodeSet::Set1,
+            3 => ScancodeSet::Set3,
+            _ => ScancodeSet::Set2,
+        };
+        let result = self.probe_scancode_sets();
+        let restored = self.set_scancode_set(original as u8);
+        let supported = result?;
+        restored?;
+        Ok(supported)
+    }
+
+    fn probe_scancode_sets(&mut self) -> Result<[bool; 3]> {
+        let mut supported = [false; 3];
+        for (set, supported) in (1..=3).zip(supported.iter_mut()) {
+            *supported = match self.set_scancode_set(set) {
+                Ok(()) => untranslate_scancode_set(self.get_scancode_set()?) == set,
+                Err(KeyboardError::Resend) | Err(KeyboardError::InvalidResponse(_)) => false,
+                Err(err) => return Err(err),
+            };
+        }
+        Ok(supported)
+    }
+
     /// Attempt to obtain a device identifier for this keyboard.
+    ///
+    /// Original AT keyboards acknowledge the command but don't send an identifier, so a timeout
+    /// after the acknowledgement is reported as [`KeyboardType::ATWithTranslation`]. A timeout
+    /// waiting for the acknowledgement itself is returned as an error. To treat that as the
+    /// keyboard being absent, use [`Keyboard::detect_keyboard_type`].
     pub fn get_keyboard_type(&mut self) -> Result<KeyboardType> {
         // First check to see if the command was acknowledged
         match self.write_command(Command::IdentifyKeyboard, None) {
@@ -127,6 +230,71 @@ impl<'c> Keyboard<'c> {
         }
     }
 
+    /// Like [`Keyboard::get_keyboard_type`], but returns the two raw identifier bytes sent by the
+    /// keyboard. This is useful for logging the ID of a keyboard reported as
+    /// [`KeyboardType::Unknown`].
+    ///
+    /// Keyboards that don't send an identifier can't be told apart here: XT keyboards cause
+    /// [`KeyboardError::Resend`] to be returned, and AT keyboards cause [`KeyboardError::Timeout`].
+    pub fn get_keyboard_id_raw(&mut self) -> Result<(u8, u8)> {
+        self.write_command(Command::IdentifyKeyboard, None)?;
+        let first_byte = self.controller.read_data()?;
+        let second_byte = self.controller.read_data()?;
+        Ok((first_byte, second_byte))
+    }
+
+    /// Like [`Keyboard::get_keyboard_type`], but returns `None` instead of
+    /// [`KeyboardError::Timeout`] if the keyboard doesn't respond, since that usually means no
+    /// keyboard is connected. Other errors are still returned.
+    pub fn detect_keyboard_type(&mut self) -> Result<Option<KeyboardType>> {
+        match self.get_keyboard_type() {
+            Ok(keyboard_type) => Ok(Some(keyboard_type)),
+            Err(KeyboardError::Timeout) => Ok(None),
+            Err(err) => Err(err),
+        }
+    }
+
+    /// Like [`Keyboard::get_keyboard_type`], but returns `fallback` if identification fails for
+    /// any reason, including the keyboard not responding at all.
+    ///
+    /// This is useful during initialization, where a best guess is preferable to giving up.
+    pub fn identify_with_fallback(&mut self, fallback: KeyboardType) -> KeyboardType {
+        self.get_keyboard_type().unwrap_or(fallback)
+    }
+
+    /// Check whether a keyboard is connected and responding to commands, using
+    /// [`Keyboard::echo`].
+    ///
+    /// The PS/2 protocol has no real support for hotplugging, so this is only a best-effort check.
+    pub fn is_present(&mut self) -> bool {
+        self.echo().is_ok()
+    }
+
+    /// Switch the numeric keypad between numeric and cursor movement mode, by setting the Num Lock
+    /// LED.
+    ///
+    /// The keyboard type must be given, since the two kinds of keyboard differ. Identify the
+    /// keyboard once using [`Keyboard::get_keyboard_type`] and reuse the result, rather than
+    /// identifying it on every call:
+    ///
+    /// - On AT and MF2 keyboards, the Num Lock LED is set or cleared using
+    ///   [`Keyboard::set_leds`]. This also turns off the other LEDs, so if you track their state,
+    ///   use [`Keyboard::set_leds`] directly instead.
+    /// - XT keyboards have no LED command, and always send the same scancodes for keypad keys.
+    ///   Nothing is sent to the keyboard, so the mode must be tracked in software when
+    ///   interpreting keypad scancodes.
+    pub fn set_numpad_mode(&mut self, keyboard_type: KeyboardType, numeric: bool) -> Result<()> {
+        if keyboard_type == KeyboardType::XT {
+            return Ok(());
+        }
+        let leds = if numeric {
+            KeyboardLedFlags::NUM_LOCK
+        } else {
+            KeyboardLedFlags::empty()
+        };
+        self.set_leds(leds)
+    }
+
     /// Set the typematic repeat rate and delay.
     ///
     /// Use the 'Repeat rate' and 'Delay' tables on
@@ -140,6 +308,18 @@ impl<'c> Keyboard<'c> {
         )
     }
 
+    /// Set the typematic repeat rate and delay using their indices in the tables linked from
+    /// [`Keyboard::set_typematic_rate_and_delay`].
+    ///
+    /// The configuration byte is laid out as follows: bits 0 to 4 hold the repeat rate index, from
+    /// `0` (30 Hz) to `31` (2 Hz), and bits 5 and 6 hold the delay index, from `0` (250 ms) to `3`
+    /// (1000 ms). Bit 7 is always 0. Returns [`KeyboardError::InvalidTypematicFrequency`] or
+    /// [`KeyboardError::InvalidTypematicDelay`] if an index is out of range.
+    pub fn set_typematic(&mut self, rate_index: u8, delay_index: u8) -> Result<()> {
+        let config = TypematicConfig::new(rate_index, delay_index)?;
+        self.set_typematic_rate_and_delay(config.bits())
+    }
+
     /// Clear the data buffer and last typematic key, then enable scancodes.
     pub fn enable_scanning(&mut self) -> Result<()> {
         self.write_command(Command::EnableScanning, None)
@@ -150,6 +330,33 @@ impl<'c> Keyboard<'c> {
         self.write_command(Command::DisableScanning, None)
     }
 
+    /// Disable and then enable scanning, which clears the keyboard's buffer and restores the
+    /// default settings.
+    ///
+    /// This is less disruptive than [`Keyboard::reset_and_self_test`], since no self-test is run,
+    /// but still leaves the keyboard's input buffer in a clean state.
+    pub fn restart_scanning(&mut self) -> Result<()> {
+        self.disable_scanning()?;
+        self.enable_scanning()
+    }
+
+    /// Disable scanning, run `f`, then enable scanning again.
+    ///
+    /// Configuration commands should be sent while scanning is disabled, so that a keypress isn't
+    /// mistaken for a response. Note that disabling scanning also restores the default settings.
+    /// Scanning is enabled again even if `f` fails, in which case the error from `f` is returned.
+    pub fn with_scanning_disabled<F, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Self) -> Result<R>,
+    {
+        self.disable_scanning()?;
+        let result = f(self);
+        let restored = self.enable_scanning();
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
     /// Reset keyboard to power-on state by clearing the data buffer and restoring all default key
     /// settings.
     pub fn set_defaults(&mut self) -> Result<()> {
@@ -157,6 +364,10 @@ impl<'c> Keyboard<'c> {
     }
 
     /// Set all keys to typematic only. This only has an effect if scancode set 3 is in use.
+    ///
+    /// Scanning should be disabled while configuring keys, so that a keypress isn't mistaken for
+    /// a response. To check that the keyboard didn't acknowledge the command more than once, use
+    /// [`Keyboard::check_extra_ack`] afterwards.
     pub fn set_all_keys_typematic(&mut self) -> Result<()> {
         self.write_command(Command::SetAllKeysTypematic, None)
     }
@@ -171,12 +382,39 @@ impl<'c> Keyboard<'c> {
         self.write_command(Command::SetAllKeysMakeOnly, None)
     }
 
+    /// Enable or disable break codes for all keys. This only has an effect if scancode set 3 is in
+    /// use.
+    ///
+    /// Enabling break codes uses [`Keyboard::set_all_keys_make_break`], and disabling them uses
+    /// [`Keyboard::set_all_keys_make_only`]. Either way, typematic repeat is disabled for all keys.
+    pub fn set_break_codes_enabled(&mut self, enabled: bool) -> Result<()> {
+        if enabled {
+            self.set_all_keys_make_break()
+        } else {
+            self.set_all_keys_make_only()
+        }
+    }
+
     /// Set all keys to typematic and make/break. This only has an effect if scancode set 3 is in
     /// use.
     pub fn set_all_keys_typematic_make_break(&mut self) -> Result<()> {
         self.write_command(Command::SetAllKeysTypematicAndMakeBreak, None)
     }
 
+    /// Enable or disable typematic repeat for all keys. This only has an effect if scancode set 3
+    /// is in use.
+    ///
+    /// Enabling typematic repeat uses [`Keyboard::set_all_keys_typematic_make_break`], and
+    /// disabling it uses [`Keyboard::set_all_keys_make_break`]. Either way, break codes are enabled
+    /// for all keys.
+    pub fn set_typematic_enabled(&mut self, enabled: bool) -> Result<()> {
+        if enabled {
+            self.set_all_keys_typematic_make_break()
+        } else {
+            self.set_all_keys_make_break()
+        }
+    }
+
     /// Set a specific key to typematic only. This only has an effect if scancode set 3 is in use.
     pub fn set_key_typematic(&mut self, scancode: u8) -> Result<()> {
         self.write_command(Command::SetKeyTypematic, Some(scancode))
@@ -192,6 +430,142 @@ impl<'c> Keyboard<'c> {
         self.write_command(Command::SetKeyMakeOnly, Some(scancode))
     }
 
+    /// Check that no extra acknowledgement follows a command that has already been acknowledged,
+    /// returning [`KeyboardError::UnexpectedAck`] with the given command byte if one arrives. This
+    /// is intended for diagnosing keyboards that misbehave while being configured using scancode
+    /// set 3, where each byte should be acknowledged exactly once.
+    ///
+    /// This waits for the full IO timeout unless a byte arrives. Bytes from the mouse are left in
+    /// the buffer, and any other byte from the keyboard is written back to the buffer using
+    /// [`Controller::write_keyboard_buffer`] so it isn't lost.
+    pub fn check_extra_ack(&mut self, command: u8) -> Result<()> {
+        match self.controller.poll_keyboard_data() {
+            Some(ack) if self.controller.is_ack(ack) => {
+                Err(KeyboardError::UnexpectedAck { command })
+            }
+            Some(other) => Ok(self.controller.write_keyboard_buffer(other)?),
+            None => Ok(()),
+        }
+    }
+
+    /// Set the behavior of each of the given keys. This only has an effect if scancode set 3 is in
+    /// use.
+    ///
+    /// Returns the number of keys configured. If a command fails, this stops immediately and
+    /// returns the number of keys that were configured before the failure along with the error.
+    pub fn configure_keys<I>(
+        &mut self,
+        keys: I,
+        behavior: KeyBehavior,
+    ) -> core::result::Result<usize, (usize, KeyboardError)>
+    where
+        I: IntoIterator<Item = u8>,
+    {
+        let mut configured = 0;
+        for scancode in keys {
+            self.set_key_behavior(scancode, behavior)
+                .map_err(|err| (configured, err))?;
+            configured += 1;
+        }
+        Ok(configured)
+    }
+
+    /// Set the behavior of each key in a list of scancode and behavior pairs. This only has an
+    /// effect if scancode set 3 is in use.
+    ///
+    /// The per-key commands accept a list of scancodes, so consecutive keys with the same behavior
+    /// are sent after a single command. A list ends when the keyboard receives another command, so
+    /// the last one is ended by whichever command is sent next. Scanning state isn't changed, so
+    /// this can be used within [`Keyboard::with_scanning_disabled`].
+    ///
+    /// Returns the number of keys configured. If a command fails, this stops immediately and
+    /// returns the number of keys that were configured before the failure along with the error.
+    pub fn bulk_set_key_behavior(
+        &mut self,
+        pairs: &[(u8, KeyBehavior)],
+    ) -> core::result::Result<usize, (usize, KeyboardError)> {
+        let mut current = None;
+        for (configured, &(scancode, behavior)) in pairs.iter().enumerate() {
+            if current != Some(behavior) {
+                let command = match behavior {
+                    KeyBehavior::Typematic => Command::SetKeyTypematic,
+                    KeyBehavior::MakeBreak => Command::SetKeyMakeBreak,
+                    KeyBehavior::MakeOnly => Command::SetKeyMakeOnly,
+                };
+                self.write_command(command, Some(scancode))
+                    .map_err(|err| (configured, err))?;
+                current = Some(behavior);
+                continue;
+            }
+            self.controller
+                .write_data(scancode)
+                .map_err(|err| (configured, err.into()))?;
+            self.check_response().map_err(|err| (configured, err))?;
+        }
+        Ok(pairs.len())
+    }
+
+    fn set_key_behavior(&mut self, scancode: u8, behavior: KeyBehavior) -> Result<()> {
+        match behavior {
+            KeyBehavior::Typematic => self.set_key_typematic(scancode),
+            KeyBehavior::MakeBreak => self.set_key_make_break(scancode),
+            KeyBehavior::MakeOnly => self.set_key_make_only(scancode),
+        }
+    }
+
+    /// Count how many distinct keys are pressed while scanning, as a hardware diagnostic. This is
+    /// not intended for use outside of testing keyboards.
+    ///
+    /// The keyboard must be using scancode set 3. All keys are set to make/break and scanning is
+    /// enabled, then make codes are counted until `expected_count` distinct keys have been pressed
+    /// or `window` has elapsed, measured in the units of the controller's [`TimeSource`]. The window
+    /// should be long enough for a person to press every key. Bytes from the mouse are ignored.
+    /// Default key settings are restored afterwards, even if a command fails.
+    pub fn verify_all_keys_responding(&mut self, expected_count: u8, window: usize) -> Result<u8> {
+        let result = self.count_responding_keys(expected_count, window);
+        let restored = self.set_defaults();
+        let count = result?;
+        restored?;
+        Ok(count)
+    }
+
+    fn count_responding_keys(&mut self, expected_count: u8, window: usize) -> Result<u8> {
+        self.set_all_keys_make_break()?;
+        self.enable_scanning()?;
+
+        let start = self.controller.elapsed_cycles();
+        let mut seen = [0u8; 32];
+        let mut count = 0;
+        let mut released = false;
+        while count < expected_count {
+            let scancode = match self.controller.service_interrupt() {
+                Some((DataSource::Keyboard, scancode)) => scancode,
+                Some((DataSource::Mouse, _)) => continue,
+                None => {
+                    let elapsed = self.controller.elapsed_cycles().wrapping_sub(start);
+                    if elapsed >= window as u64 {
+                        break;
+                    }
+                    continue;
+                }
+            };
+            if scancode == BREAK_PREFIX {
+                released = true;
+                continue;
+            }
+            if released {
+                released = false;
+                continue;
+            }
+            let (index, bit) = (scancode as usize / 8, 1 << (scancode % 8));
+            if seen[index] & bit == 0 {
+                seen[index] |= bit;
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
     /// Get the last byte sent by the keyboard.
     pub fn resend_last_byte(&mut self) -> Result<u8> {
         self.controller.write_data(Command::ResendLastByte as u8)?;
@@ -201,16 +575,677 @@ impl<'c> Keyboard<'c> {
         }
     }
 
+    fn read_self_test_result(&mut self) -> Result<u8> {
+        self.controller.read_data().map_err(|err| match err {
+            ControllerError::Timeout => KeyboardError::SelfTestTimeout,
+            err => err.into(),
+        })
+    }
+
     /// Reset the keyboard and perform a Basic Assurance Test.
     ///
-    /// Returns [`KeyboardError::SelfTestFailed`] if the test fails.
+    /// Returns [`KeyboardError::SelfTestFailed`] with the keyboard's response if the test fails.
+    /// This is usually `0xfc` or `0xfd`, but any response other than success is treated as a
+    /// failure.
+    ///
+    /// Some keyboards send extra acknowledgements before the test result. Up to 2 of these are
+    /// skipped, each waiting at most the controller's timeout for the next byte. Any more are
+    /// reported as [`KeyboardError::InvalidResponse`].
+    ///
+    /// Returns [`KeyboardError::SelfTestTimeout`] if no result arrives in time. The test can take
+    /// several hundred milliseconds, so this usually means the keyboard is still starting up or the
+    /// timeout is too short: wait a few milliseconds and retry. If it keeps timing out, there is
+    /// probably no keyboard connected. A [`KeyboardError::SelfTestFailed`] result, on the other
+    /// hand, is not worth retrying.
     pub fn reset_and_self_test(&mut self) -> Result<()> {
         self.write_command(Command::ResetAndSelfTest, None)?;
-        match self.controller.read_data()? {
+        let mut response = self.read_self_test_result()?;
+        for _ in 0..MAX_EXTRA_RESET_ACKS {
+            if response != COMMAND_ACKNOWLEDGED {
+                break;
+            }
+            response = self.read_self_test_result()?;
+        }
+        match response {
             SELF_TEST_PASSED => Ok(()),
-            SELF_TEST_FAILED => Err(KeyboardError::SelfTestFailed),
             RESEND => Err(KeyboardError::Resend),
-            other => Err(KeyboardError::InvalidResponse(other)),
+            COMMAND_ACKNOWLEDGED => Err(KeyboardError::InvalidResponse(COMMAND_ACKNOWLEDGED)),
+            response => Err(KeyboardError::SelfTestFailed { response }),
         }
     }
+
+    /// Read a scancode in set 1 from the data buffer, returning the key and whether it was a break
+    /// code.
+    ///
+    /// In set 1, a break code is the make code with [`set1::BREAK_BIT`] set. A key whose make code
+    /// follows an [`EXTENDED_PREFIX`] byte is returned as [`ScancodeSequence::Extended`]. If the
+    /// read times out after the prefix, this is remembered by the controller, so the next scancode
+    /// read is still reported as extended. Other multi-byte sequences, such as the one sent by
+    /// Pause, need a [`KeyEventDecoder`].
+    pub fn read_set1_scancode(&mut self) -> Result<(ScancodeSequence, bool)> {
+        loop {
+            let byte = self.controller.read_data()?;
+            if let Some(scancode) = self.decode_set1_byte(byte) {
+                return Ok(scancode);
+            }
+        }
+    }
+
+    fn decode_set1_byte(&mut self, byte: u8) -> Option<(ScancodeSequence, bool)> {
+        if byte == EXTENDED_PREFIX {
+            self.controller.set_extended_prefix();
+            return None;
+        }
+        let make_code = byte & !set1::BREAK_BIT;
+        let scancode = if self.controller.take_extended_prefix() {
+            ScancodeSequence::Extended(make_code)
+        } else {
+            ScancodeSequence::Normal(make_code)
+        };
+        Some((scancode, byte & set1::BREAK_BIT != 0))
+    }
+
+    /// Read the next key from the data buffer if there is one, returning it as a set 1 scancode
+    /// along with whether it was a break code, like [`Keyboard::read_set1_scancode`]. Returns
+    /// `None` without waiting if the data buffer holds no complete key.
+    ///
+    /// This is a minimal alternative to [`KeyEventDecoder`] for simple consoles. Bytes from the
+    /// mouse are discarded. An [`EXTENDED_PREFIX`] byte at the end of the buffer is remembered by
+    /// the controller, and applied to the key read by the next call. Other multi-byte sequences,
+    /// such as the one sent by Pause, need the full decoder.
+    pub fn next_key(&mut self) -> Result<Option<(ScancodeSequence, bool)>> {
+        loop {
+            let status = self.controller.read_status();
+            if !status.contains(ControllerStatusFlags::OUTPUT_FULL) {
+                return Ok(None);
+            }
+            let byte = self.controller.read_data()?;
+            if status.contains(ControllerStatusFlags::MOUSE_OUTPUT_FULL) {
+                continue;
+            }
+            if let Some(scancode) = self.decode_set1_byte(byte) {
+                return Ok(Some(scancode));
+            }
+        }
+    }
+
+    /// Read a scancode in set 2 from the data buffer, returning the key and whether it was a break
+    /// code, meaning [`BREAK_PREFIX`] came before it.
+    ///
+    /// Like in [`Keyboard::read_set1_scancode`], a key following an [`EXTENDED_PREFIX`] byte is
+    /// returned as [`ScancodeSequence::Extended`]. If the read times out after either prefix, this
+    /// is remembered by the controller and applied to the next scancode read.
+    pub fn read_set2_scancode(&mut self) -> Result<(ScancodeSequence, bool)> {
+        loop {
+            match self.controller.read_data()? {
+                BREAK_PREFIX => self.controller.set_break_prefix(),
+                EXTENDED_PREFIX => self.controller.set_extended_prefix(),
+                byte => {
+                    let scancode = if self.controller.take_extended_prefix() {
+                        ScancodeSequence::Extended(byte)
+                    } else {
+                        ScancodeSequence::Normal(byte)
+                    };
+                    return Ok((scancode, self.controller.take_break_prefix()));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock::{self, COMMAND, DATA};
+
+    #[test]
+    fn reset_with_extra_ack_test() {
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED, SELF_TEST_PASSED]);
+        let mut controller = unsafe { Controller::new() };
+        controller.keyboard().reset_and_self_test().unwrap();
+
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED]);
+        assert!(matches!(
+            controller.keyboard().reset_and_self_test(),
+            Err(KeyboardError::SelfTestTimeout)
+        ));
+
+        // An endless stream of acknowledgements doesn't hang
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED; 16]);
+        assert!(matches!(
+            controller.keyboard().reset_and_self_test(),
+            Err(KeyboardError::InvalidResponse(COMMAND_ACKNOWLEDGED))
+        ));
+    }
+
+    #[test]
+    fn reset_failure_response_test() {
+        for &response in &[0xfc, 0x37] {
+            mock::reset();
+            mock::push_data(&[COMMAND_ACKNOWLEDGED, response]);
+            let mut controller = unsafe { Controller::new() };
+            assert!(matches!(
+                controller.keyboard().reset_and_self_test(),
+                Err(KeyboardError::SelfTestFailed { response: actual }) if actual == response
+            ));
+        }
+    }
+
+    #[test]
+    fn alternate_ack_test() {
+        mock::reset();
+        mock::push_data(&[0xfb]);
+        let mut controller = unsafe { Controller::new() };
+        assert!(matches!(
+            controller.keyboard().set_defaults(),
+            Err(KeyboardError::InvalidResponse(0xfb))
+        ));
+
+        mock::reset();
+        mock::push_data(&[0xfb]);
+        controller.set_alternate_acks(&[0xfb]);
+        controller.keyboard().set_defaults().unwrap();
+    }
+
+    #[test]
+    fn restart_scanning_test() {
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED; 2]);
+        let mut controller = unsafe { Controller::new() };
+        controller.keyboard().restart_scanning().unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xf5), (DATA, 0xf4)]);
+    }
+
+    #[test]
+    fn get_keyboard_id_raw_test() {
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED, 0xab, 0x12]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller.keyboard().get_keyboard_id_raw().unwrap(),
+            (0xab, 0x12)
+        );
+        assert_eq!(mock::writes(), [(DATA, 0xf2)]);
+
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED]);
+        assert!(matches!(
+            controller.keyboard().get_keyboard_id_raw(),
+            Err(KeyboardError::Timeout)
+        ));
+    }
+
+    #[test]
+    fn identify_with_fallback_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller
+                .keyboard()
+                .identify_with_fallback(KeyboardType::MF2),
+            KeyboardType::MF2
+        );
+
+        mock::reset();
+        mock::push_data(&[RESEND]);
+        assert_eq!(
+            controller
+                .keyboard()
+                .identify_with_fallback(KeyboardType::MF2),
+            KeyboardType::XT
+        );
+    }
+
+    #[test]
+    fn detect_keyboard_type_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.keyboard().detect_keyboard_type().unwrap(), None);
+        assert!(!controller.keyboard().is_present());
+
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED]);
+        assert_eq!(
+            controller.keyboard().detect_keyboard_type().unwrap(),
+            Some(KeyboardType::ATWithTranslation)
+        );
+
+        mock::reset();
+        mock::push_data(&[ECHO]);
+        assert!(controller.keyboard().is_present());
+    }
+
+    #[test]
+    fn check_echo_roundtrip_test() {
+        mock::reset();
+        mock::push_data(&[RESEND, RESEND, ECHO]);
+        let mut controller = unsafe { Controller::new() };
+        controller.keyboard().check_echo_roundtrip().unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xee); 3]);
+
+        mock::reset();
+        mock::push_data(&[RESEND, 0x00, RESEND]);
+        assert!(matches!(
+            controller.keyboard().check_echo_roundtrip(),
+            Err(KeyboardError::Resend)
+        ));
+    }
+
+    #[test]
+    fn check_extra_ack_test() {
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        controller.keyboard().set_all_keys_make_break().unwrap();
+        controller.keyboard().check_extra_ack(0xf8).unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xf8)]);
+
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED]);
+        controller.keyboard().set_key_make_only(0x1c).unwrap();
+        assert!(matches!(
+            controller.keyboard().check_extra_ack(0xfd),
+            Err(KeyboardError::UnexpectedAck { command: 0xfd })
+        ));
+        assert_eq!(mock::pending(), 0);
+
+        // A keypress is written back to the buffer
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED, 0x1c]);
+        controller.keyboard().set_all_keys_make_only().unwrap();
+        controller.keyboard().check_extra_ack(0xf9).unwrap();
+        assert_eq!(
+            mock::writes(),
+            [(DATA, 0xf9), (COMMAND, 0xd2), (DATA, 0x1c)]
+        );
+    }
+
+    #[test]
+    fn set3_command_leaves_mouse_data_test() {
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_mouse_data(&[0x08]);
+        let mut controller = unsafe { Controller::new() };
+        controller.keyboard().set_all_keys_make_break().unwrap();
+        assert_eq!(mock::pending(), 1);
+        controller.keyboard().check_extra_ack(0xf8).unwrap();
+        assert_eq!(mock::pending(), 1);
+        assert_eq!(mock::writes(), [(DATA, 0xf8)]);
+    }
+
+    #[test]
+    fn test_scancode_set_support_test() {
+        mock::reset();
+        // Get original set 2, then set 1 reads back as translated set 1, set 2 reads back as set
+        // 1, and set 3 is rejected
+        for reply in &[
+            &[COMMAND_ACKNOWLEDGED][..],
+            &[COMMAND_ACKNOWLEDGED, 0x02],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED, 0x43],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED, 0x01],
+            &[COMMAND_ACKNOWLEDGED],
+            &[RESEND],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+        ] {
+            mock::push_reply(reply);
+        }
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller.keyboard().test_scancode_set_support().unwrap(),
+            [true, false, false]
+        );
+        assert_eq!(mock::writes().last(), Some(&(DATA, 0x02)));
+        assert_eq!(mock::pending(), 0);
+
+        // An invalid original set falls back to set 2, which is restored when probing fails
+        mock::reset();
+        for reply in &[
+            &[COMMAND_ACKNOWLEDGED][..],
+            &[COMMAND_ACKNOWLEDGED, 0x00],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+            &[COMMAND_ACKNOWLEDGED],
+        ] {
+            mock::push_reply(reply);
+        }
+        assert!(matches!(
+            controller.keyboard().test_scancode_set_support(),
+            Err(KeyboardError::Timeout)
+        ));
+        assert_eq!(
+            mock::writes(),
+            [
+                (DATA, 0xf0),
+                (DATA, 0x00),
+                (DATA, 0xf0),
+                (DATA, 0x01),
+                (DATA, 0xf0),
+                (DATA, 0x00),
+                (DATA, 0xf0),
+                (DATA, 0x02)
+            ]
+        );
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn verify_all_keys_responding_test() {
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED, 0x1c, 0xf0, 0x1c]);
+        mock::append_mouse_reply(&[0x08, 0x32, 0x00]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        // The window is independent of the IO timeout
+        let mut controller = unsafe { Controller::with_timeout(1) };
+        assert_eq!(
+            controller
+                .keyboard()
+                .verify_all_keys_responding(104, 100)
+                .unwrap(),
+            1
+        );
+        assert_eq!(mock::writes(), [(DATA, 0xf8), (DATA, 0xf4), (DATA, 0xf6)]);
+
+        // Defaults are restored when a command fails
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[RESEND]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        assert!(matches!(
+            controller.keyboard().verify_all_keys_responding(104, 100),
+            Err(KeyboardError::Resend)
+        ));
+        assert_eq!(mock::writes(), [(DATA, 0xf8), (DATA, 0xf4), (DATA, 0xf6)]);
+    }
+
+    #[test]
+    fn set_typematic_test() {
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED; 2]);
+        let mut controller = unsafe { Controller::new() };
+        controller.keyboard().set_typematic(0x0b, 1).unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xf3), (DATA, 0x2b)]);
+
+        assert!(matches!(
+            controller.keyboard().set_typematic(32, 0),
+            Err(KeyboardError::InvalidTypematicFrequency(32))
+        ));
+        assert!(matches!(
+            controller.keyboard().set_typematic(0, 4),
+            Err(KeyboardError::InvalidTypematicDelay(4))
+        ));
+        assert_eq!(mock::writes().len(), 2);
+    }
+
+    #[test]
+    fn set_numpad_mode_test() {
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .keyboard()
+            .set_numpad_mode(KeyboardType::MF2, true)
+            .unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xed), (DATA, 0x02)]);
+
+        mock::reset();
+        controller
+            .keyboard()
+            .set_numpad_mode(KeyboardType::XT, false)
+            .unwrap();
+        assert!(mock::writes().is_empty());
+    }
+
+    #[test]
+    fn set_break_codes_enabled_test() {
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .keyboard()
+            .set_break_codes_enabled(false)
+            .unwrap();
+        controller.keyboard().set_break_codes_enabled(true).unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xf9), (DATA, 0xf8)]);
+    }
+
+    #[test]
+    fn set_typematic_enabled_test() {
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        controller.keyboard().set_typematic_enabled(true).unwrap();
+        controller.keyboard().set_typematic_enabled(false).unwrap();
+        assert_eq!(mock::writes(), [(DATA, 0xfa), (DATA, 0xf8)]);
+    }
+
+    #[test]
+    fn get_and_set_scancode_set_test() {
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED, 0x43]);
+        mock::push_data(&[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        let previous = controller
+            .keyboard()
+            .get_and_set_scancode_set(ScancodeSet::Set2)
+            .unwrap();
+        assert_eq!(previous, ScancodeSet::Set1);
+        assert_eq!(
+            mock::writes(),
+            [(DATA, 0xf0), (DATA, 0x00), (DATA, 0xf0), (DATA, 0x02)]
+        );
+
+        mock::reset();
+        mock::push_data(&[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED, 0x07]);
+        mock::push_data(&[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED]);
+        let previous = controller
+            .keyboard()
+            .get_and_set_scancode_set(ScancodeSet::Set3)
+            .unwrap();
+        assert_eq!(previous, ScancodeSet::Set2);
+    }
+
+    #[test]
+    fn with_scanning_disabled_test() {
+        mock::reset();
+        for _ in 0..4 {
+            mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        }
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .keyboard()
+            .with_scanning_disabled(|keyboard| keyboard.set_scancode_set(3))
+            .unwrap();
+        assert_eq!(
+            mock::writes(),
+            [(DATA, 0xf5), (DATA, 0xf0), (DATA, 0x03), (DATA, 0xf4)]
+        );
+
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[RESEND]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        let result = controller
+            .keyboard()
+            .with_scanning_disabled(|keyboard| keyboard.set_scancode_set(3));
+        assert!(matches!(result, Err(KeyboardError::Resend)));
+        assert_eq!(mock::writes(), [(DATA, 0xf5), (DATA, 0xf0), (DATA, 0xf4)]);
+    }
+
+    #[test]
+    fn bulk_set_key_behavior_test() {
+        mock::reset();
+        for _ in 0..5 {
+            mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        }
+        let mut controller = unsafe { Controller::new() };
+        let result = controller.keyboard().bulk_set_key_behavior(&[
+            (0x1c, KeyBehavior::MakeOnly),
+            (0x32, KeyBehavior::MakeOnly),
+            (0x21, KeyBehavior::Typematic),
+        ]);
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(
+            mock::writes(),
+            [
+                (DATA, 0xfd),
+                (DATA, 0x1c),
+                (DATA, 0x32),
+                (DATA, 0xfb),
+                (DATA, 0x21)
+            ]
+        );
+        assert_eq!(mock::pending(), 0);
+
+        mock::reset();
+        for &reply in &[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED, RESEND] {
+            mock::push_reply(&[reply]);
+        }
+        let result = controller.keyboard().bulk_set_key_behavior(&[
+            (0x1c, KeyBehavior::MakeBreak),
+            (0x32, KeyBehavior::MakeBreak),
+        ]);
+        assert!(matches!(result, Err((1, KeyboardError::Resend))));
+    }
+
+    #[test]
+    fn configure_keys_test() {
+        mock::reset();
+        for _ in 0..4 {
+            mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        }
+        let mut controller = unsafe { Controller::new() };
+        let result = controller
+            .keyboard()
+            .configure_keys([0x1c, 0x32], KeyBehavior::MakeOnly);
+        assert_eq!(result.unwrap(), 2);
+        assert_eq!(
+            mock::writes(),
+            [(DATA, 0xfd), (DATA, 0x1c), (DATA, 0xfd), (DATA, 0x32)]
+        );
+    }
+
+    #[test]
+    fn configure_keys_failure_test() {
+        mock::reset();
+        for &reply in &[COMMAND_ACKNOWLEDGED, COMMAND_ACKNOWLEDGED, RESEND] {
+            mock::push_reply(&[reply]);
+        }
+        let mut controller = unsafe { Controller::new() };
+        let result = controller
+            .keyboard()
+            .configure_keys([0x1c, 0x32, 0x21], KeyBehavior::Typematic);
+        assert!(matches!(result, Err((1, KeyboardError::Resend))));
+    }
+
+    #[test]
+    fn read_set1_scancode_test() {
+        mock::reset();
+        mock::push_data(&[0x1e, 0x9e, 0xe0, 0x48, 0xe0, 0xc8]);
+        let mut controller = unsafe { Controller::new() };
+        let mut keyboard = controller.keyboard();
+        for &expected in &[
+            (ScancodeSequence::Normal(0x1e), false),
+            (ScancodeSequence::Normal(0x1e), true),
+            (ScancodeSequence::Extended(0x48), false),
+            (ScancodeSequence::Extended(0x48), true),
+        ] {
+            assert_eq!(keyboard.read_set1_scancode().unwrap(), expected);
+        }
+        assert!(matches!(
+            keyboard.read_set1_scancode(),
+            Err(KeyboardError::Timeout)
+        ));
+
+        // The prefix is remembered after a timeout
+        mock::push_data(&[0xe0]);
+        assert!(matches!(
+            keyboard.read_set1_scancode(),
+            Err(KeyboardError::Timeout)
+        ));
+        mock::push_data(&[0x1d, 0x1d]);
+        assert_eq!(
+            keyboard.read_set1_scancode().unwrap(),
+            (ScancodeSequence::Extended(0x1d), false)
+        );
+        assert_eq!(
+            keyboard.read_set1_scancode().unwrap(),
+            (ScancodeSequence::Normal(0x1d), false)
+        );
+    }
+
+    #[test]
+    fn read_set2_scancode_test() {
+        mock::reset();
+        mock::push_data(&[0x1c, 0xf0, 0x1c, 0xe0, 0xf0, 0x75, 0xf0]);
+        let mut controller = unsafe { Controller::new() };
+        for &expected in &[
+            (ScancodeSequence::Normal(0x1c), false),
+            (ScancodeSequence::Normal(0x1c), true),
+            (ScancodeSequence::Extended(0x75), true),
+        ] {
+            assert_eq!(
+                controller.keyboard().read_set2_scancode().unwrap(),
+                expected
+            );
+        }
+        assert!(matches!(
+            controller.keyboard().read_set2_scancode(),
+            Err(KeyboardError::Timeout)
+        ));
+
+        mock::push_data(&[0x1c, 0x1c]);
+        assert_eq!(
+            controller.keyboard().read_set2_scancode().unwrap(),
+            (ScancodeSequence::Normal(0x1c), true)
+        );
+        assert_eq!(
+            controller.keyboard().read_set2_scancode().unwrap(),
+            (ScancodeSequence::Normal(0x1c), false)
+        );
+    }
+
+    #[test]
+    fn next_key_test() {
+        mock::reset();
+        mock::push_data(&[0x1e]);
+        mock::push_mouse_data(&[0x08, 0x01, 0x02]);
+        mock::push_data(&[0x9e, 0xe0]);
+        let mut controller = unsafe { Controller::new() };
+        let mut keyboard = controller.keyboard();
+        assert_eq!(
+            keyboard.next_key().unwrap(),
+            Some((ScancodeSequence::Normal(0x1e), false))
+        );
+        assert_eq!(
+            keyboard.next_key().unwrap(),
+            Some((ScancodeSequence::Normal(0x1e), true))
+        );
+        assert_eq!(keyboard.next_key().unwrap(), None);
+        assert_eq!(mock::pending(), 0);
+
+        mock::push_data(&[0x48]);
+        assert_eq!(
+            keyboard.next_key().unwrap(),
+            Some((ScancodeSequence::Extended(0x48), false))
+        );
+        assert_eq!(keyboard.next_key().unwrap(), None);
+    }
 }
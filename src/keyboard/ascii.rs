@@ -0,0 +1,97 @@
+use crate::scancodes::{set1, set2};
+
+/// The key that produces a printable ASCII character on a US keyboard layout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct AsciiKey {
+    /// The make code of the key in scancode set 1.
+    pub(crate) set1: u8,
+    /// The make code of the key in scancode set 2.
+    pub(crate) set2: u8,
+    /// Whether shift must be held while pressing the key.
+    pub(crate) shift: bool,
+}
+
+impl AsciiKey {
+    /// Look up the key for a printable ASCII character, from `' '` to `'~'`.
+    pub(crate) fn from_char(character: char) -> Option<Self> {
+        let (base, shift) = match character {
+            'A'..='Z' => (character.to_ascii_lowercase(), true),
+            '~' => ('`', true),
+            '!' => ('1', true),
+            '@' => ('2', true),
+            '#' => ('3', true),
+            '$' => ('4', true),
+            '%' => ('5', true),
+            '^' => ('6', true),
+            '&' => ('7', true),
+            '*' => ('8', true),
+            '(' => ('9', true),
+            ')' => ('0', true),
+            '_' => ('-', true),
+            '+' => ('=', true),
+            '{' => ('[', true),
+            '}' => (']', true),
+            '|' => ('\\', true),
+            ':' => (';', true),
+            '"' => ('\'', true),
+            '<' => (',', true),
+            '>' => ('.', true),
+            '?' => ('/', true),
+            other => (other, false),
+        };
+
+        let (set1, set2) = match base {
+            '`' => (set1::BACKTICK, set2::BACKTICK),
+            '1' => (set1::KEY_1, set2::KEY_1),
+            '2' => (set1::KEY_2, set2::KEY_2),
+            '3' => (set1::KEY_3, set2::KEY_3),
+            '4' => (set1::KEY_4, set2::KEY_4),
+            '5' => (set1::KEY_5, set2::KEY_5),
+            '6' => (set1::KEY_6, set2::KEY_6),
+            '7' => (set1::KEY_7, set2::KEY_7),
+            '8' => (set1::KEY_8, set2::KEY_8),
+            '9' => (set1::KEY_9, set2::KEY_9),
+            '0' => (set1::KEY_0, set2::KEY_0),
+            '-' => (set1::MINUS, set2::MINUS),
+            '=' => (set1::EQUALS, set2::EQUALS),
+            'q' => (set1::Q, set2::Q),
+            'w' => (set1::W, set2::W),
+            'e' => (set1::E, set2::E),
+            'r' => (set1::R, set2::R),
+            't' => (set1::T, set2::T),
+            'y' => (set1::Y, set2::Y),
+            'u' => (set1::U, set2::U),
+            'i' => (set1::I, set2::I),
+            'o' => (set1::O, set2::O),
+            'p' => (set1::P, set2::P),
+            '[' => (set1::LEFT_BRACKET, set2::LEFT_BRACKET),
+            ']' => (set1::RIGHT_BRACKET, set2::RIGHT_BRACKET),
+            '\\' => (set1::BACKSLASH, set2::BACKSLASH),
+            'a' => (set1::A, set2::A),
+            's' => (set1::S, set2::S),
+            'd' => (set1::D, set2::D),
+            'f' => (set1::F, set2::F),
+            'g' => (set1::G, set2::G),
+            'h' => (set1::H, set2::H),
+            'j' => (set1::J, set2::J),
+            'k' => (set1::K, set2::K),
+            'l' => (set1::L, set2::L),
+            ';' => (set1::SEMICOLON, set2::SEMICOLON),
+            '\'' => (set1::APOSTROPHE, set2::APOSTROPHE),
+            'z' => (set1::Z, set2::Z),
+            'x' => (set1::X, set2::X),
+            'c' => (set1::C, set2::C),
+            'v' => (set1::V, set2::V),
+            'b' => (set1::B, set2::B),
+            'n' => (set1::N, set2::N),
+            'm' => (set1::M, set2::M),
+            ',' => (set1::COMMA, set2::COMMA),
+            '.' => (set1::PERIOD, set2::PERIOD),
+            '/' => (set1::SLASH, set2::SLASH),
+            ' ' => (set1::SPACE, set2::SPACE),
+            _ => return None,
+        };
+
+        Some(Self { set1, set2, shift })
+    }
+}
@@ -0,0 +1,11 @@
+/// How an individual key reports being pressed and released. Only used by scancode set 3, where
+/// each key can be configured separately.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyBehavior {
+    /// The key repeats while held, and doesn't send a break code when released.
+    Typematic,
+    /// The key sends a make code when pressed and a break code when released, without repeating.
+    MakeBreak,
+    /// The key only sends a make code when pressed.
+    MakeOnly,
+}
@@ -0,0 +1,245 @@
+use crate::{
+    keyboard::ScancodeSet,
+    scancodes::{set1, set2, EXTENDED_PREFIX},
+};
+
+/// The first byte of the sequence sent when Pause is pressed, in scancode sets 1 and 2.
+const PAUSE_PREFIX: u8 = 0xe1;
+
+/// The scancode identifying a key, without any break code markers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScancodeSequence {
+    /// A key identified by a single make code.
+    Normal(u8),
+    /// A key whose make code is prefixed with [`EXTENDED_PREFIX`].
+    Extended(u8),
+    /// The Pause key, which sends a multi-byte sequence in scancode sets 1 and 2.
+    Pause,
+}
+
+/// A key press or release, decoded from the scancodes sent by the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    /// The key that was pressed or released.
+    pub scancode: ScancodeSequence,
+    /// Whether the key was pressed, as opposed to released.
+    pub pressed: bool,
+    /// Whether this is a typematic repeat of a key that is being held down, rather than the
+    /// initial press. Always `false` for releases.
+    pub repeat: bool,
+    /// The scancode set the event was decoded from.
+    pub set: ScancodeSet,
+}
+
+/// Decodes [`KeyEvent`]s from scancode bytes received one at a time.
+///
+/// In set 1, a break code is the make code with its high bit set. In sets 2 and 3, a break code is
+/// the make code prefixed with [`set2::BREAK_PREFIX`]. In set 3, break codes may also be disabled
+/// per key, in which case only press events are produced for those keys.
+///
+/// While a key is held down, the keyboard repeatedly sends its make code. The decoder remembers
+/// the last key pressed, and marks further presses of it as repeats until it is released or
+/// another key is pressed.
+///
+/// # Examples
+/// ```
+/// use ps2::{KeyEventDecoder, ScancodeSequence, ScancodeSet};
+///
+/// let mut decoder = KeyEventDecoder::new(ScancodeSet::Set2);
+/// assert!(decoder.push(0xf0).is_none());
+/// let event = decoder.push(0x1c).unwrap();
+/// assert_eq!(event.scancode, ScancodeSequence::Normal(0x1c));
+/// assert!(!event.pressed);
+/// ```
+#[derive(Debug)]
+pub struct KeyEventDecoder {
+    set: ScancodeSet,
+    extended: bool,
+    released: bool,
+    pause_bytes_remaining: usize,
+    last_pressed: Option<ScancodeSequence>,
+}
+
+impl KeyEventDecoder {
+    /// Create a decoder for scancodes in the given set.
+    pub const fn new(set: ScancodeSet) -> Self {
+        Self {
+            set,
+            extended: false,
+            released: false,
+            pause_bytes_remaining: 0,
+            last_pressed: None,
+        }
+    }
+
+    /// Add a byte received from the keyboard, returning an event if it completed one.
+    pub fn push(&mut self, byte: u8) -> Option<KeyEvent> {
+        if self.pause_bytes_remaining > 0 {
+            self.pause_bytes_remaining -= 1;
+            return match self.pause_bytes_remaining {
+                0 => Some(self.event(ScancodeSequence::Pause, true)),
+                _ => None,
+            };
+        }
+
+        match (self.set, byte) {
+            (ScancodeSet::Set1, PAUSE_PREFIX) => {
+                self.pause_bytes_remaining = set1::PAUSE_PRESSED.len() - 1;
+                None
+            }
+            (ScancodeSet::Set2, PAUSE_PREFIX) => {
+                self.pause_bytes_remaining = set2::PAUSE_PRESSED.len() - 1;
+                None
+            }
+            (ScancodeSet::Set1, EXTENDED_PREFIX) | (ScancodeSet::Set2, EXTENDED_PREFIX) => {
+                self.extended = true;
+                None
+            }
+            (ScancodeSet::Set2, set2::BREAK_PREFIX) | (ScancodeSet::Set3, set2::BREAK_PREFIX) => {
+                self.released = true;
+                None
+            }
+            (ScancodeSet::Set1, _) => {
+                let pressed = byte & set1::BREAK_BIT == 0;
+                Some(self.key_event(byte & !set1::BREAK_BIT, pressed))
+            }
+            (_, _) => {
+                let pressed = !self.released;
+                Some(self.key_event(byte, pressed))
+            }
+        }
+    }
+
+    /// Discard any partially received scancode sequence.
+    pub fn reset(&mut self) {
+        self.extended = false;
+        self.released = false;
+        self.pause_bytes_remaining = 0;
+    }
+
+    fn key_event(&mut self, make_code: u8, pressed: bool) -> KeyEvent {
+        let scancode = if self.extended {
+            ScancodeSequence::Extended(make_code)
+        } else {
+            ScancodeSequence::Normal(make_code)
+        };
+        self.event(scancode, pressed)
+    }
+
+    fn event(&mut self, scancode: ScancodeSequence, pressed: bool) -> KeyEvent {
+        self.reset();
+        let repeat = pressed && self.last_pressed == Some(scancode);
+        if pressed {
+            self.last_pressed = Some(scancode);
+        } else if self.last_pressed == Some(scancode) {
+            self.last_pressed = None;
+        }
+        KeyEvent {
+            scancode,
+            pressed,
+            repeat,
+            set: self.set,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use std::vec::Vec;
+
+    fn decode(set: ScancodeSet, bytes: &[u8]) -> Vec<(ScancodeSequence, bool)> {
+        let mut decoder = KeyEventDecoder::new(set);
+        bytes
+            .iter()
+            .filter_map(|&byte| decoder.push(byte))
+            .map(|event| {
+                assert_eq!(event.set, set);
+                (event.scancode, event.pressed)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn decode_set1_test() {
+        assert_eq!(
+            decode(
+                ScancodeSet::Set1,
+                &[0x1e, 0x9e, 0xe0, 0x1d, 0xe0, 0x9d, 0xe1, 0x1d, 0x45, 0xe1, 0x9d, 0xc5]
+            ),
+            [
+                (ScancodeSequence::Normal(set1::A), true),
+                (ScancodeSequence::Normal(set1::A), false),
+                (
+                    ScancodeSequence::Extended(set1::extended::RIGHT_CONTROL),
+                    true
+                ),
+                (
+                    ScancodeSequence::Extended(set1::extended::RIGHT_CONTROL),
+                    false
+                ),
+                (ScancodeSequence::Pause, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_set2_test() {
+        let mut bytes = std::vec![0x1c, 0xf0, 0x1c, 0xe0, 0x14, 0xe0, 0xf0, 0x14];
+        bytes.extend_from_slice(&set2::PAUSE_PRESSED);
+        assert_eq!(
+            decode(ScancodeSet::Set2, &bytes),
+            [
+                (ScancodeSequence::Normal(set2::A), true),
+                (ScancodeSequence::Normal(set2::A), false),
+                (
+                    ScancodeSequence::Extended(set2::extended::RIGHT_CONTROL),
+                    true
+                ),
+                (
+                    ScancodeSequence::Extended(set2::extended::RIGHT_CONTROL),
+                    false
+                ),
+                (ScancodeSequence::Pause, true),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_set3_test() {
+        // 0xe0 is an ordinary key in set 3
+        assert_eq!(
+            decode(ScancodeSet::Set3, &[0x1c, 0xf0, 0x1c, 0xe0]),
+            [
+                (ScancodeSequence::Normal(0x1c), true),
+                (ScancodeSequence::Normal(0x1c), false),
+                (ScancodeSequence::Normal(0xe0), true),
+            ]
+        );
+    }
+
+    #[test]
+    fn repeat_test() {
+        let mut decoder = KeyEventDecoder::new(ScancodeSet::Set2);
+        let events: Vec<(bool, bool)> = [0x1c, 0x1c, 0xf0, 0x1c, 0x1c]
+            .iter()
+            .filter_map(|&byte| decoder.push(byte))
+            .map(|event| (event.pressed, event.repeat))
+            .collect();
+        assert_eq!(
+            events,
+            [(true, false), (true, true), (false, false), (true, false)]
+        );
+
+        // Pressing another key stops the first one from repeating
+        let mut decoder = KeyEventDecoder::new(ScancodeSet::Set1);
+        let repeats: Vec<bool> = [0x1e, 0x1f, 0x1e]
+            .iter()
+            .filter_map(|&byte| decoder.push(byte))
+            .map(|event| event.repeat)
+            .collect();
+        assert_eq!(repeats, [false, false, false]);
+    }
+}
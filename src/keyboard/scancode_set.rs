@@ -0,0 +1,9 @@
+/// A keyboard scancode set. For more details, see
+/// [here](https://web.archive.org/web/20200616182211/https://www.win.tue.nl/~aeb/linux/kbd/scancodes-10.html#ss10.1).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ScancodeSet {
+    Set1 = 1,
+    Set2 = 2,
+    Set3 = 3,
+}
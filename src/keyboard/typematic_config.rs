@@ -0,0 +1,101 @@
+use crate::error::KeyboardError;
+
+/// Repeat rates in Hz, indexed by the low 5 bits of the typematic configuration byte.
+const RATES_HZ: [f32; 32] = [
+    30.0, 26.7, 24.0, 21.8, 20.7, 18.5, 17.1, 16.0, 15.0, 13.3, 12.0, 10.9, 10.0, 9.2, 8.6, 8.0,
+    7.5, 6.7, 6.0, 5.5, 5.0, 4.6, 4.3, 4.0, 3.7, 3.3, 3.0, 2.7, 2.5, 2.3, 2.1, 2.0,
+];
+
+/// A typematic repeat rate and delay, as sent with
+/// [`Keyboard::set_typematic_rate_and_delay`](crate::Keyboard::set_typematic_rate_and_delay).
+///
+/// The configuration byte holds the repeat rate index in bits 0 to 4, and the delay index in bits
+/// 5 and 6. For more details, see the 'Repeat rate' and 'Delay' tables
+/// [here](https://web.archive.org/web/20091128232820/http://www.computer-engineering.org/index.php?title=PS/2_Keyboard_Interface#Command_Set).
+///
+/// # Examples
+/// ```
+/// use ps2::TypematicConfig;
+///
+/// let config = TypematicConfig::from_bits(0x2b);
+/// assert_eq!((config.rate_hz(), config.delay_ms()), (10.9, 500));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TypematicConfig {
+    rate_index: u8,
+    delay_index: u8,
+}
+
+impl TypematicConfig {
+    /// Create a configuration from a repeat rate index between 0 (30 Hz) and 31 (2 Hz), and a
+    /// delay index between 0 (250 ms) and 3 (1000 ms).
+    ///
+    /// Returns [`KeyboardError::InvalidTypematicFrequency`] or
+    /// [`KeyboardError::InvalidTypematicDelay`] if an index is out of range.
+    pub const fn new(rate_index: u8, delay_index: u8) -> Result<Self, KeyboardError> {
+        if rate_index > 0b11111 {
+            return Err(KeyboardError::InvalidTypematicFrequency(rate_index));
+        }
+        if delay_index > 0b11 {
+            return Err(KeyboardError::InvalidTypematicDelay(delay_index));
+        }
+        Ok(Self {
+            rate_index,
+            delay_index,
+        })
+    }
+
+    /// Decode a configuration byte. The most significant bit is ignored.
+    pub const fn from_bits(byte: u8) -> Self {
+        Self {
+            rate_index: byte & 0b11111,
+            delay_index: (byte >> 5) & 0b11,
+        }
+    }
+
+    /// Encode this configuration as a configuration byte.
+    pub const fn bits(&self) -> u8 {
+        self.delay_index << 5 | self.rate_index
+    }
+
+    /// The repeat rate, in Hz.
+    pub const fn rate_hz(&self) -> f32 {
+        RATES_HZ[self.rate_index as usize]
+    }
+
+    /// The delay before repeating starts, in milliseconds.
+    pub const fn delay_ms(&self) -> u16 {
+        (self.delay_index as u16 + 1) * 250
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_test() {
+        for &(byte, rate_hz, delay_ms) in &[
+            (0x00, 30.0, 250),
+            (0x2b, 10.9, 500),
+            (0x44, 20.7, 750),
+            (0x7f, 2.0, 1000),
+            (0xff, 2.0, 1000),
+        ] {
+            let config = TypematicConfig::from_bits(byte);
+            assert_eq!((config.rate_hz(), config.delay_ms()), (rate_hz, delay_ms));
+        }
+    }
+
+    #[test]
+    fn round_trip_test() {
+        for byte in 0..=0x7f {
+            let config = TypematicConfig::from_bits(byte);
+            assert_eq!(config.bits(), byte);
+            assert_eq!(
+                TypematicConfig::new(byte & 0b11111, byte >> 5).unwrap(),
+                config
+            );
+        }
+    }
+}
@@ -1,5 +1,4 @@
 #![no_std]
-#![feature(const_mut_refs)]
 #![warn(rust_2018_idioms)]
 //! This crate provides comprehensive low-level access to the PS/2 controller and PS/2 devices. It
 //! uses a poll-based approach with a timeout to read and write data to the IO ports.
@@ -78,6 +77,9 @@
 //! }
 //! ```
 //!
+//! [`Controller::initialize`] performs steps 3 to 9 of this process, leaving interrupts disabled
+//! and the devices un-reset.
+//!
 //! Once the controller is initialized and the devices are working properly, they will place input
 //! in the data buffer at IO port `0x60`. You can read from this buffer at any time using
 //! [`Controller::read_data`]. If you plan on using a poll-based approach to handle device input,
@@ -116,19 +118,38 @@
 //! [netcore2k_keyboard]: https://web.archive.org/web/20201023082815/http://helppc.netcore2k.net/hardware/keyboard-commands
 
 pub use self::{
-    controller::Controller,
-    keyboard::{Keyboard, KeyboardType},
-    mouse::{Mouse, MouseType},
+    controller::{Controller, ControllerRam, DataSource, InitResult, InternalRamByte},
+    device::Ps2Device,
+    diagnostics::ScancodeDiagnostics,
+    io::{IoDirection, IoPort},
+    keyboard::{
+        KeyBehavior, KeyEvent, KeyEventDecoder, Keyboard, KeyboardType, ScancodeSequence,
+        ScancodeSet, TypematicConfig,
+    },
+    mouse::{
+        Cursor, Mouse, MouseMode, MousePacket, MousePacketAssembler, MouseResolution,
+        MouseSampleRate, MouseScaler, MouseType, PacketAssembler,
+    },
+    presence::{HasKeyboard, HasMouse, NoKeyboard, NoMouse},
+    time_source::{CycleCounter, TimeSource},
 };
 
 mod controller;
+mod device;
+mod diagnostics;
+mod io;
 mod keyboard;
 mod mouse;
+mod presence;
+mod time_source;
+
+#[cfg(test)]
+mod mock;
 
 pub mod error;
 pub mod flags;
+pub mod scancodes;
 
 const COMMAND_ACKNOWLEDGED: u8 = 0xfa;
 const SELF_TEST_PASSED: u8 = 0xaa;
-const SELF_TEST_FAILED: u8 = 0xfc;
 const RESEND: u8 = 0xfe;
@@ -0,0 +1,227 @@
+//! A fake IO port backend used in place of real port IO when running tests.
+//!
+//! Bytes queued with [`push_data`] or [`push_mouse_data`] are served from the data port, and the
+//! status register reports them as available. Replies queued with [`push_reply`] only become
+//! available once a byte is written to the data port, like a device responding to a command. Every
+//! byte written to any port is recorded and can be inspected with [`writes`]. For tests that issue
+//! many controller commands, [`emulate_controller`] answers them like a real controller would.
+//! State is kept per thread, so tests may run in parallel.
+#![allow(dead_code)]
+
+extern crate std;
+
+use core::marker::PhantomData;
+use std::{cell::RefCell, collections::VecDeque, vec::Vec};
+
+use crate::flags::ControllerStatusFlags;
+
+pub(crate) const DATA: u16 = 0x60;
+pub(crate) const COMMAND: u16 = 0x64;
+
+struct State {
+    data_port: u16,
+    command_port: u16,
+    output: VecDeque<(u8, ControllerStatusFlags)>,
+    replies: VecDeque<Vec<(u8, ControllerStatusFlags)>>,
+    writes: Vec<(u16, u8)>,
+    input_full: bool,
+    status_reads: usize,
+    emulated_config: Option<u8>,
+    awaiting_config: bool,
+}
+
+impl State {
+    const fn new() -> Self {
+        Self {
+            data_port: DATA,
+            command_port: COMMAND,
+            output: VecDeque::new(),
+            replies: VecDeque::new(),
+            writes: Vec::new(),
+            input_full: false,
+            status_reads: 0,
+            emulated_config: None,
+            awaiting_config: false,
+        }
+    }
+
+    fn emulate_command(&mut self, config: u8, command: u8) {
+        let (config, response) = match command {
+            0x20 => (config, Some(config)),
+            0x60 => {
+                self.awaiting_config = true;
+                (config, None)
+            }
+            0xa7 => (config | 0x20, None),
+            0xa8 => (config & !0x20, None),
+            0xa9 | 0xab => (config, Some(0x00)),
+            0xaa => (config, Some(0x55)),
+            0xad => (config | 0x10, None),
+            0xae => (config & !0x10, None),
+            _ => (config, None),
+        };
+        self.emulated_config = Some(config);
+        if let Some(response) = response {
+            self.output
+                .push_back((response, ControllerStatusFlags::empty()));
+        }
+    }
+}
+
+std::thread_local! {
+    static STATE: RefCell<State> = const { RefCell::new(State::new()) };
+}
+
+/// Clear all queued data and recorded writes.
+pub(crate) fn reset() {
+    STATE.with(|state| *state.borrow_mut() = State::new());
+}
+
+/// Change which port addresses are treated as the data and command ports.
+pub(crate) fn set_ports(data_port: u16, command_port: u16) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        state.data_port = data_port;
+        state.command_port = command_port;
+    });
+}
+
+/// Queue a byte to be read from the data port, reporting the given extra status flags while it
+/// is waiting in the buffer.
+pub(crate) fn push_with_status(byte: u8, status: ControllerStatusFlags) {
+    STATE.with(|state| state.borrow_mut().output.push_back((byte, status)));
+}
+
+/// Queue bytes to be read from the data port as if they came from the keyboard or controller.
+pub(crate) fn push_data(bytes: &[u8]) {
+    for &byte in bytes {
+        push_with_status(byte, ControllerStatusFlags::empty());
+    }
+}
+
+/// Queue bytes to be read from the data port as if they came from the mouse.
+pub(crate) fn push_mouse_data(bytes: &[u8]) {
+    for &byte in bytes {
+        push_with_status(byte, ControllerStatusFlags::MOUSE_OUTPUT_FULL);
+    }
+}
+
+/// Queue a reply from the keyboard, which is made available to read once the next unanswered
+/// byte is written to the data port.
+pub(crate) fn push_reply(bytes: &[u8]) {
+    let reply = bytes
+        .iter()
+        .map(|&byte| (byte, ControllerStatusFlags::empty()))
+        .collect();
+    STATE.with(|state| state.borrow_mut().replies.push_back(reply));
+}
+
+/// Append bytes from the mouse to the most recently queued reply, as if the mouse sent them
+/// while the keyboard was responding.
+pub(crate) fn append_mouse_reply(bytes: &[u8]) {
+    STATE.with(|state| {
+        let mut state = state.borrow_mut();
+        let reply = state.replies.back_mut().expect("no reply to append to");
+        reply.extend(
+            bytes
+                .iter()
+                .map(|&byte| (byte, ControllerStatusFlags::MOUSE_OUTPUT_FULL)),
+        );
+    });
+}
+
+/// Keep the input buffer full, so that every write times out.
+pub(crate) fn set_input_full(input_full: bool) {
+    STATE.with(|state| state.borrow_mut().input_full = input_full);
+}
+
+/// Respond to controller commands written to the command port, starting with the given
+/// configuration byte. Self-tests always pass, and enabling or disabling a device updates the
+/// configuration byte.
+pub(crate) fn emulate_controller(config: u8) {
+    STATE.with(|state| state.borrow_mut().emulated_config = Some(config));
+}
+
+/// The configuration byte of the emulated controller.
+pub(crate) fn config() -> u8 {
+    STATE.with(|state| state.borrow().emulated_config.unwrap())
+}
+
+/// All bytes written so far, along with the port they were written to.
+pub(crate) fn writes() -> Vec<(u16, u8)> {
+    STATE.with(|state| state.borrow().writes.clone())
+}
+
+/// The number of bytes queued for reading that have not been read yet.
+pub(crate) fn pending() -> usize {
+    STATE.with(|state| state.borrow().output.len())
+}
+
+/// The number of times the status register has been read.
+pub(crate) fn status_reads() -> usize {
+    STATE.with(|state| state.borrow().status_reads)
+}
+
+#[derive(Debug)]
+pub(crate) struct Port<T> {
+    port: u16,
+    phantom: PhantomData<T>,
+}
+
+impl<T> Port<T> {
+    pub(crate) const fn new(port: u16) -> Self {
+        Self {
+            port,
+            phantom: PhantomData,
+        }
+    }
+}
+
+impl Port<u8> {
+    pub(crate) unsafe fn read(&mut self) -> u8 {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            if self.port == state.data_port {
+                state.output.pop_front().map_or(0, |(byte, _)| byte)
+            } else if self.port == state.command_port {
+                state.status_reads += 1;
+                let mut status = match state.output.front() {
+                    Some(&(_, status)) => status | ControllerStatusFlags::OUTPUT_FULL,
+                    None => ControllerStatusFlags::empty(),
+                };
+                status.set(ControllerStatusFlags::INPUT_FULL, state.input_full);
+                status.bits()
+            } else {
+                panic!("read from unexpected port {:#x}", self.port);
+            }
+        })
+    }
+
+    pub(crate) unsafe fn write(&mut self, value: u8) {
+        STATE.with(|state| {
+            let mut state = state.borrow_mut();
+            assert!(
+                self.port == state.data_port || self.port == state.command_port,
+                "write to unexpected port {:#x}",
+                self.port
+            );
+            state.writes.push((self.port, value));
+            if let Some(config) = state.emulated_config {
+                if self.port == state.command_port {
+                    state.emulate_command(config, value);
+                    return;
+                }
+                if state.awaiting_config {
+                    state.emulated_config = Some(value);
+                    state.awaiting_config = false;
+                    return;
+                }
+            }
+            if self.port == state.data_port {
+                if let Some(reply) = state.replies.pop_front() {
+                    state.output.extend(reply);
+                }
+            }
+        });
+    }
+}
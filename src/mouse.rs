@@ -1,16 +1,42 @@
+use core::convert::TryFrom;
+
 use crate::{
     controller::Controller,
-    error::MouseError,
+    error::{ControllerError, MouseError},
     flags::{MouseMovementFlags, MouseStatusFlags},
-    COMMAND_ACKNOWLEDGED, RESEND, SELF_TEST_FAILED, SELF_TEST_PASSED,
+    presence::{HasKeyboard, HasMouse},
+    time_source::{CycleCounter, TimeSource},
+    RESEND, SELF_TEST_PASSED,
 };
 
-pub use self::mouse_type::MouseType;
+pub use self::{
+    cursor::Cursor,
+    mouse_mode::MouseMode,
+    mouse_resolution::MouseResolution,
+    mouse_sample_rate::MouseSampleRate,
+    mouse_type::MouseType,
+    packet::{MousePacket, MousePacketAssembler, PacketAssembler},
+    scaler::MouseScaler,
+};
 
+mod cursor;
+mod mouse_mode;
+mod mouse_resolution;
+mod mouse_sample_rate;
 mod mouse_type;
+mod packet;
+mod scaler;
 
 const VALID_RESOLUTIONS: [u8; 4] = [0, 1, 2, 3];
 const VALID_SAMPLE_RATES: [u8; 7] = [10, 20, 40, 60, 80, 100, 200];
+const SELF_TEST_FAILED: u8 = 0xfc;
+const MAX_PACKET_RESYNC_DISCARDS: usize = 16;
+const PRESENCE_ACK_TIMEOUT_DIVISOR: usize = 10;
+
+// Interprets the low 4 bits of a byte as a two's complement integer, ignoring the high bits
+const fn sign_extend_4bit(val: u8) -> i8 {
+    ((val << 4) as i8) >> 4
+}
 
 type Result<T> = core::result::Result<T, MouseError>;
 
@@ -47,19 +73,19 @@ enum Command {
 /// let mut mouse = controller.mouse();
 /// ```
 #[derive(Debug)]
-pub struct Mouse<'c> {
-    controller: &'c mut Controller,
+pub struct Mouse<'c, T = CycleCounter, K = HasKeyboard> {
+    controller: &'c mut Controller<T, K, HasMouse>,
 }
 
 // TODO: Support Intellimouse extensions
-impl<'c> Mouse<'c> {
-    pub(crate) const fn new(controller: &'c mut Controller) -> Self {
+impl<'c, T: TimeSource, K> Mouse<'c, T, K> {
+    pub(crate) const fn new(controller: &'c mut Controller<T, K, HasMouse>) -> Self {
         Self { controller }
     }
 
     fn check_response(&mut self) -> Result<()> {
         match self.controller.read_data()? {
-            COMMAND_ACKNOWLEDGED => Ok(()),
+            ack if self.controller.is_ack(ack) => Ok(()),
             RESEND => Err(MouseError::Resend),
             other => Err(MouseError::InvalidResponse(other)),
         }
@@ -69,7 +95,8 @@ impl<'c> Mouse<'c> {
         self.controller.write_mouse(command as u8)?;
         self.check_response()?;
         if let Some(data) = data {
-            self.controller.write_data(data)?;
+            // Parameter bytes must also be routed to the mouse
+            self.controller.write_mouse(data)?;
             self.check_response()?;
         }
         Ok(())
@@ -99,33 +126,119 @@ impl<'c> Mouse<'c> {
         if !VALID_RESOLUTIONS.contains(&resolution) {
             return Err(MouseError::InvalidResolution(resolution));
         }
-        // Ok to unwrap since we already checked for existence in VALID_RESOLUTIONS.
-        // Also safe to cast to u8 since VALID_RESOLUTIONS has only 4 elements
-        let resolution_index = VALID_RESOLUTIONS
-            .iter()
-            .position(|&n| n == resolution)
-            .unwrap() as u8;
-        self.write_command(Command::SetResolution, Some(resolution_index))
+        self.write_command(Command::SetResolution, Some(resolution))
+    }
+
+    /// Set mouse resolution, then request a status packet to confirm that the mouse applied it.
+    ///
+    /// Returns [`MouseError::InvalidResolution`] with the reported resolution if it doesn't match.
+    /// This can be used to detect mice that don't support every resolution.
+    pub fn set_resolution_and_verify(&mut self, resolution: MouseResolution) -> Result<()> {
+        self.set_resolution(resolution as u8)?;
+        let (_, actual, _) = self.get_status_packet()?;
+        if actual != resolution {
+            return Err(MouseError::InvalidResolution(actual as u8));
+        }
+        Ok(())
+    }
+
+    /// Check which resolutions the mouse supports, by setting each one and reading it back from a
+    /// status packet. Bit N of the result is set if resolution N is supported, using the values
+    /// of [`MouseResolution`].
+    ///
+    /// The original resolution is restored afterwards, even if probing fails.
+    pub fn test_resolution_support(&mut self) -> Result<u8> {
+        let (_, original, _) = self.get_status_packet()?;
+        let result = self.probe_resolutions();
+        let restored = self.set_resolution(original as u8);
+        let supported = result?;
+        restored?;
+        Ok(supported)
+    }
+
+    fn probe_resolutions(&mut self) -> Result<u8> {
+        let mut supported = 0;
+        for &resolution in &[
+            MouseResolution::OneCountPerMm,
+            MouseResolution::TwoCountsPerMm,
+            MouseResolution::FourCountsPerMm,
+            MouseResolution::EightCountsPerMm,
+        ] {
+            match self.set_resolution_and_verify(resolution) {
+                Ok(()) => supported |= 1 << resolution as u8,
+                Err(MouseError::InvalidResolution(_)) | Err(MouseError::Resend) => {}
+                Err(err) => return Err(err),
+            }
+        }
+        Ok(supported)
+    }
+
+    /// Find the highest resolution the mouse supports, using
+    /// [`Mouse::test_resolution_support`].
+    ///
+    /// Returns [`MouseError::InvalidResolution`] with the original resolution if the mouse doesn't
+    /// appear to support any resolution.
+    pub fn max_supported_resolution(&mut self) -> Result<MouseResolution> {
+        let supported = self.test_resolution_support()?;
+        match supported.checked_ilog2() {
+            Some(highest) => MouseResolution::try_from(highest as u8),
+            None => {
+                let (_, original, _) = self.get_status_packet()?;
+                Err(MouseError::InvalidResolution(original as u8))
+            }
+        }
     }
 
     /// Request a status packet from the mouse and reset the movement counters.
     ///
-    /// The first byte returned is a bitfield, the second byte is the mouse resolution, and the
-    /// third is the sample rate.
-    pub fn get_status_packet(&mut self) -> Result<(MouseStatusFlags, u8, u8)> {
+    /// The first byte returned is a bitfield, the second is the mouse resolution, and the third is
+    /// the sample rate. If the mouse reports a resolution that isn't valid,
+    /// [`MouseError::InvalidResolution`] is returned with the raw byte. The raw byte of a valid
+    /// resolution can be recovered by casting it to `u8`. If any of the reserved bits of the status
+    /// byte are set, [`MouseError::InvalidStatusFlags`] is returned with the raw byte.
+    pub fn get_status_packet(&mut self) -> Result<(MouseStatusFlags, MouseResolution, u8)> {
         self.write_command(Command::StatusRequest, None)?;
-        let status = MouseStatusFlags::from_bits_truncate(self.controller.read_data()?);
+        // Read the whole packet before validating it, so no bytes are left in the buffer
+        let status = self.controller.read_data()?;
         let resolution = self.controller.read_data()?;
         let sample_rate = self.controller.read_data()?;
-        if !VALID_RESOLUTIONS.contains(&resolution) {
-            return Err(MouseError::InvalidResolution(resolution));
+        if !MouseStatusFlags::is_valid_byte(status) {
+            return Err(MouseError::InvalidStatusFlags(status));
         }
+        let status = MouseStatusFlags::from_bits_truncate(status);
+        let resolution = MouseResolution::try_from(resolution)?;
         if !VALID_SAMPLE_RATES.contains(&sample_rate) {
             return Err(MouseError::InvalidSampleRate(sample_rate));
         }
         Ok((status, resolution, sample_rate))
     }
 
+    /// Reset the movement counters without changing any other state.
+    ///
+    /// This is implemented by requesting a status packet, which resets the counters as a side
+    /// effect, and discarding its contents without validating them.
+    pub fn reset_counters(&mut self) -> Result<()> {
+        self.write_command(Command::StatusRequest, None)?;
+        for _ in 0..3 {
+            self.controller.read_data()?;
+        }
+        Ok(())
+    }
+
+    /// Get the current sample rate of the mouse from a status packet. Like
+    /// [`Mouse::get_status_packet`], this resets the movement counters.
+    pub fn get_sample_rate(&mut self) -> Result<MouseSampleRate> {
+        let (_, _, sample_rate) = self.get_status_packet()?;
+        MouseSampleRate::try_from(sample_rate)
+    }
+
+    /// Get the current resolution of the mouse from a status packet. Like
+    /// [`Mouse::get_status_packet`], this resets the movement counters.
+    pub fn get_current_resolution(&mut self) -> Result<MouseResolution> {
+        let (_, resolution, _) = self.get_status_packet()?;
+        Ok(resolution)
+    }
+
     /// Reset mouse movement counters and enter streaming mode.
     ///
     /// Read more about modes
@@ -146,6 +259,33 @@ impl<'c> Mouse<'c> {
         self.read_data_packet()
     }
 
+    /// Request `N` movement data packets from the mouse, one at a time. This is intended for
+    /// remote mode, where the mouse only sends packets on request.
+    ///
+    /// The packet size is taken from [`Controller::mouse_packet_size`]. If a request fails, this
+    /// stops immediately and returns the number of packets that were read before the failure
+    /// along with the error.
+    pub fn request_packets<const N: usize>(
+        &mut self,
+    ) -> core::result::Result<[MousePacket; N], (usize, MouseError)> {
+        let mut packets = [MousePacket {
+            flags: MouseMovementFlags::empty(),
+            dx: 0,
+            dy: 0,
+            dz: None,
+            button4: false,
+            button5: false,
+        }; N];
+        let mouse_type = self.controller.mouse_type();
+        for (read, packet) in packets.iter_mut().enumerate() {
+            self.write_command(Command::ReadData, None)
+                .map_err(|err| (read, err))?;
+            let (bytes, len, _) = self.read_data_packet_raw().map_err(|err| (read, err))?;
+            *packet = MousePacket::from_bytes(&bytes[..len], mouse_type);
+        }
+        Ok(packets)
+    }
+
     /// Read an existing movement data packet directly from the data buffer.
     ///
     /// The first byte returned is a bitfield, and the other two bytes are 9-bit two's complement
@@ -153,20 +293,87 @@ impl<'c> Mouse<'c> {
     /// the last packet was sent.
     ///
     /// This does **not** send any commands to the mouse. This is useful in interrupt handlers when
-    /// we just want to read the data sent by the mouse.
+    /// we just want to read the data sent by the mouse. If the mouse sends 4-byte packets, as
+    /// reported by [`Controller::mouse_packet_size`], the fourth byte is read as well, so that the
+    /// next packet is read from the right place.
     pub fn read_data_packet(&mut self) -> Result<(MouseMovementFlags, i16, i16)> {
-        let movement_flags = MouseMovementFlags::from_bits_truncate(self.controller.read_data()?);
-        let mut x_movement = self.controller.read_data()? as u16;
-        let mut y_movement = self.controller.read_data()? as u16;
+        let (_, _, packet) = self.read_data_packet_raw()?;
+        Ok(packet)
+    }
 
-        if movement_flags.contains(MouseMovementFlags::X_SIGN_BIT) {
-            x_movement |= 0xff00;
+    /// Like [`Mouse::read_data_packet`], but also returns the raw bytes of the packet and how many
+    /// of them were read. This is useful for logging when the decoded movement looks wrong.
+    #[allow(clippy::type_complexity)]
+    pub fn read_data_packet_raw(
+        &mut self,
+    ) -> Result<([u8; 4], usize, (MouseMovementFlags, i16, i16))> {
+        let mut bytes = [0; 4];
+        let mouse_type = self.controller.mouse_type();
+        let len = mouse_type.packet_size();
+        for byte in bytes[..len].iter_mut() {
+            *byte = self.controller.read_data()?;
         }
-        if movement_flags.contains(MouseMovementFlags::Y_SIGN_BIT) {
-            y_movement |= 0xff00;
+
+        let packet = MousePacket::from_bytes(&bytes[..len], mouse_type);
+        Ok((bytes, len, (packet.flags, packet.dx, packet.dy)))
+    }
+
+    /// Read a movement data packet directly from the data buffer, discarding leading bytes that
+    /// `is_first_byte` rejects so that a packet is only decoded from the right place.
+    ///
+    /// [`MousePacket::is_valid_first_byte`] is a suitable predicate for every mouse type, but a
+    /// stricter one can be given for mice known to send other patterns. The packet size is taken
+    /// from [`Controller::mouse_packet_size`]. At most 16 bytes are discarded, after which
+    /// [`MouseError::InvalidResponse`] is returned with the last rejected byte.
+    ///
+    /// Like [`Mouse::read_data_packet`], this does **not** send any commands to the mouse.
+    pub fn read_packet<F>(&mut self, is_first_byte: F) -> Result<MousePacket>
+    where
+        F: Fn(u8) -> bool,
+    {
+        let mut bytes = [0; 4];
+        let mut discarded = 0;
+        loop {
+            bytes[0] = self.controller.read_data()?;
+            if is_first_byte(bytes[0]) {
+                break;
+            }
+            discarded += 1;
+            if discarded >= MAX_PACKET_RESYNC_DISCARDS {
+                return Err(MouseError::InvalidResponse(bytes[0]));
+            }
+        }
+
+        let mouse_type = self.controller.mouse_type();
+        let len = mouse_type.packet_size();
+        for byte in bytes[1..len].iter_mut() {
+            *byte = self.controller.read_data()?;
+        }
+        Ok(MousePacket::from_bytes(&bytes[..len], mouse_type))
+    }
+
+    /// Wait for a movement data packet from the mouse and read it from the data buffer.
+    ///
+    /// Unlike [`Mouse::read_data_packet`], this checks that each byte came from the mouse before
+    /// reading it. Bytes from the keyboard that are in the buffer first are discarded, and counted
+    /// by [`Mouse::skipped_bytes`]. This is useful when polling in stream mode with interrupts
+    /// disabled.
+    pub fn wait_for_data_packet(&mut self) -> Result<MousePacket> {
+        let mut bytes = [0; 4];
+        let mouse_type = self.controller.mouse_type();
+        for byte in bytes[..mouse_type.packet_size()].iter_mut() {
+            *byte = self.controller.read_mouse_data()?;
         }
+        Ok(MousePacket::from_bytes(
+            &bytes[..mouse_type.packet_size()],
+            mouse_type,
+        ))
+    }
 
-        Ok((movement_flags, x_movement as i16, y_movement as i16))
+    /// The total number of non-mouse bytes discarded by [`Mouse::wait_for_data_packet`] on this
+    /// controller.
+    pub const fn skipped_bytes(&self) -> usize {
+        self.controller.skipped_bytes()
     }
 
     /// Reset mouse movement counters and exit wrap mode, entering the mode the mouse was in
@@ -194,10 +401,50 @@ impl<'c> Mouse<'c> {
         self.write_command(Command::SetRemoteMode, None)
     }
 
+    /// Reset mouse movement counters and enter the given mode, using [`Mouse::set_stream_mode`],
+    /// [`Mouse::set_remote_mode`], or [`Mouse::set_wrap_mode`].
+    ///
+    /// To leave wrap mode, use [`Mouse::reset_wrap_mode`] instead, since no other commands are
+    /// recognized in wrap mode.
+    pub fn set_mode(&mut self, mode: MouseMode) -> Result<()> {
+        match mode {
+            MouseMode::Stream => self.set_stream_mode(),
+            MouseMode::Remote => self.set_remote_mode(),
+            MouseMode::Wrap => self.set_wrap_mode(),
+        }
+    }
+
     /// Attempt to obtain a device identifier for this mouse.
+    ///
+    /// A timeout is returned as an error. To treat it as the mouse being absent, use
+    /// [`Mouse::detect_mouse_type`].
+    ///
+    /// The type is recorded by the controller, so that movement data packets are read with the
+    /// right size. See [`Controller::mouse_packet_size`].
     pub fn get_mouse_type(&mut self) -> Result<MouseType> {
+        let mouse_type = MouseType::from(self.get_mouse_id_raw()?);
+        self.controller.set_mouse_type(mouse_type);
+        Ok(mouse_type)
+    }
+
+    /// Like [`Mouse::get_mouse_type`], but returns the raw device ID sent by the mouse. This is
+    /// useful for logging the ID of a mouse reported as [`MouseType::Unknown`].
+    ///
+    /// Unlike [`Mouse::get_mouse_type`], the ID isn't recorded on the controller.
+    pub fn get_mouse_id_raw(&mut self) -> Result<u8> {
         self.write_command(Command::GetDeviceID, None)?;
-        Ok(MouseType::from(self.controller.read_data()?))
+        Ok(self.controller.read_data()?)
+    }
+
+    /// Like [`Mouse::get_mouse_type`], but returns `None` instead of [`MouseError::Timeout`] if the
+    /// mouse doesn't respond, since that usually means no mouse is connected. Other errors are
+    /// still returned.
+    pub fn detect_mouse_type(&mut self) -> Result<Option<MouseType>> {
+        match self.get_mouse_type() {
+            Ok(mouse_type) => Ok(Some(mouse_type)),
+            Err(MouseError::Timeout) => Ok(None),
+            Err(err) => Err(err),
+        }
     }
 
     /// Set the mouse sample rate and reset the movement counters.
@@ -210,6 +457,36 @@ impl<'c> Mouse<'c> {
         self.write_command(Command::SetSampleRate, Some(sample_rate))
     }
 
+    /// Set each of the given sample rates in order.
+    ///
+    /// Mouse extensions are enabled by sending particular sequences of sample rates, such as those
+    /// given by [`MouseType::sample_rate_sequence`]. All rates are validated before any are sent,
+    /// and [`MouseError::InvalidSampleRate`] is returned for the first invalid one.
+    pub fn send_sample_rate_sequence(&mut self, rates: &[u8]) -> Result<()> {
+        if let Some(&rate) = rates.iter().find(|rate| !VALID_SAMPLE_RATES.contains(rate)) {
+            return Err(MouseError::InvalidSampleRate(rate));
+        }
+        for &rate in rates {
+            self.set_sample_rate(rate)?;
+        }
+        Ok(())
+    }
+
+    /// Set the mouse sample rate, then request a status packet to confirm that the mouse applied
+    /// it.
+    ///
+    /// Returns [`MouseError::InvalidSampleRate`] with the reported rate if it doesn't match. Note
+    /// that some USB-emulated mice clamp the sample rate to a maximum value, so this may fail on
+    /// those devices for higher rates.
+    pub fn set_sample_rate_and_verify(&mut self, sample_rate: MouseSampleRate) -> Result<()> {
+        self.set_sample_rate(sample_rate as u8)?;
+        let (_, _, actual) = self.get_status_packet()?;
+        if actual != sample_rate as u8 {
+            return Err(MouseError::InvalidSampleRate(actual));
+        }
+        Ok(())
+    }
+
     /// Enable data reporting and reset the movement counters.
     ///
     /// This only affects data reporting in stream mode.
@@ -225,6 +502,84 @@ impl<'c> Mouse<'c> {
         self.write_command(Command::DisableDataReporting, None)
     }
 
+    /// Run the given configuration commands with data reporting disabled, then enable data
+    /// reporting again if `reporting_enabled` is `true`.
+    ///
+    /// If the mouse streams a movement packet while it's being configured, the packet can be
+    /// mistaken for a response to one of the commands. Disabling data reporting first avoids this.
+    /// Any movement bytes that arrive before the mouse acknowledges that are discarded. The mouse
+    /// can't report whether data reporting was enabled without risking the same confusion, so the
+    /// caller must say whether to enable it afterwards.
+    ///
+    /// Data reporting is restored even if `f` fails, in which case the error from `f` is returned.
+    pub fn configure<F, R>(&mut self, reporting_enabled: bool, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Self) -> Result<R>,
+    {
+        self.quiesce()?;
+        let result = f(self);
+        let restored = if reporting_enabled {
+            self.enable_data_reporting()
+        } else {
+            Ok(())
+        };
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
+    // Disables data reporting while the mouse may be streaming, returning the number of movement
+    // bytes discarded
+    fn quiesce(&mut self) -> Result<usize> {
+        let mut discarded = self.controller.discard_mouse_data();
+        self.controller
+            .write_mouse(Command::DisableDataReporting as u8)?;
+        // Bytes of a packet the mouse was already sending may arrive before the acknowledgement
+        let mut skipped = 0;
+        loop {
+            match self.controller.read_data()? {
+                ack if self.controller.is_ack(ack) => break,
+                RESEND => return Err(MouseError::Resend),
+                _ if skipped < MAX_PACKET_RESYNC_DISCARDS => skipped += 1,
+                other => return Err(MouseError::InvalidResponse(other)),
+            }
+        }
+        discarded += skipped + self.controller.discard_mouse_data();
+        Ok(discarded)
+    }
+
+    /// Switch to remote mode, run `f`, then switch back to stream mode.
+    ///
+    /// This is useful for requesting individual movement packets with
+    /// [`Mouse::request_data_packet`] while the mouse is normally in stream mode. Stream mode is
+    /// restored even if `f` fails, in which case the error from `f` is returned.
+    pub fn with_remote_mode<F, R>(&mut self, f: F) -> Result<R>
+    where
+        F: FnOnce(&mut Self) -> Result<R>,
+    {
+        self.set_remote_mode()?;
+        let result = f(self);
+        let restored = self.set_stream_mode();
+        let value = result?;
+        restored?;
+        Ok(value)
+    }
+
+    /// Recover from a lost or corrupted movement data packet, returning the number of buffered
+    /// mouse bytes that were discarded.
+    ///
+    /// Data reporting is disabled like in [`Mouse::configure`], discarding any mouse bytes waiting
+    /// in the data buffer or received before the mouse acknowledges. If `reporting_enabled` is
+    /// `true`, data reporting is then enabled again, which resets the movement counters, so the
+    /// next byte received from the mouse starts a new packet.
+    pub fn resync(&mut self, reporting_enabled: bool) -> Result<usize> {
+        let discarded = self.quiesce()?;
+        if reporting_enabled {
+            self.enable_data_reporting()?;
+        }
+        Ok(discarded)
+    }
+
     /// Set defaults, clear movement counters, and enter stream mode.
     ///
     /// Default settings are as follows: sampling rate = 100 samples/second,
@@ -233,6 +588,13 @@ impl<'c> Mouse<'c> {
         self.write_command(Command::SetDefaults, None)
     }
 
+    /// Set defaults, then enable data reporting, so that the mouse starts streaming movement
+    /// packets with its default settings. See [`Mouse::set_defaults`].
+    pub fn set_defaults_and_enable_reporting(&mut self) -> Result<()> {
+        self.set_defaults()?;
+        self.enable_data_reporting()
+    }
+
     /// Request that the mouse resend the last transmitted byte or packet.
     ///
     /// Currently, this does not return any data, since the resent data may be one or more bytes in
@@ -244,18 +606,580 @@ impl<'c> Mouse<'c> {
             .write_mouse(Command::ResendLastPacket as u8)?)
     }
 
-    /// Reset the mouse and perform a Basic Assurance Test.
+    /// Check whether a mouse is connected and responding to commands.
+    ///
+    /// This sends a status request and returns `false` if the mouse doesn't acknowledge it. The
+    /// PS/2 protocol has no real support for hotplugging, so this is only a best-effort check. A
+    /// mouse that has been reconnected will usually need to be reset and configured again.
+    ///
+    /// The acknowledgement is awaited with a fraction of the controller's timeout, so that polling
+    /// for a missing mouse stays cheap. The original timeout is restored afterwards.
+    pub fn is_present(&mut self) -> bool {
+        let timeout = self.controller.timeout();
+        self.controller
+            .set_timeout((timeout / PRESENCE_ACK_TIMEOUT_DIVISOR).max(1));
+        let acknowledged = self.write_command(Command::StatusRequest, None);
+        self.controller.set_timeout(timeout);
+        if acknowledged.is_err() {
+            return false;
+        }
+        // Consume the status packet so it isn't mistaken for movement data later
+        (0..3).all(|_| self.controller.read_data().is_ok())
+    }
+
+    /// Reset the mouse and perform a Basic Assurance Test, returning the type the mouse reports
+    /// after the reset.
     ///
-    /// Returns [`MouseError::SelfTestFailed`] if the test fails.
-    pub fn reset_and_self_test(&mut self) -> Result<()> {
+    /// Returns [`MouseError::SelfTestTimeout`] if the mouse doesn't report the result of the test
+    /// in time, which may just mean it's slow to start up, so resetting it again may help.
+    /// Returns [`MouseError::SelfTestFailed`] with the mouse's response if the test fails, or
+    /// [`MouseError::UnexpectedDeviceId`] if the device ID sent after the test doesn't correspond
+    /// to a known [`MouseType`]. The device ID is only read after a response of `0xaa` or `0xfc`,
+    /// since the mouse doesn't send one otherwise.
+    pub fn reset_and_self_test(&mut self) -> Result<MouseType> {
         self.write_command(Command::ResetAndSelfTest, None)?;
-        let result = match self.controller.read_data()? {
-            SELF_TEST_PASSED => Ok(()),
-            SELF_TEST_FAILED => Err(MouseError::SelfTestFailed),
-            RESEND => Err(MouseError::Resend),
-            other => Err(MouseError::InvalidResponse(other)),
-        };
-        let _device_id = self.controller.read_data()?;
-        result
+        let response = self.controller.read_data().map_err(|err| match err {
+            ControllerError::Timeout => MouseError::SelfTestTimeout,
+            err => err.into(),
+        })?;
+        match response {
+            SELF_TEST_PASSED | SELF_TEST_FAILED => {}
+            RESEND => return Err(MouseError::Resend),
+            response => return Err(MouseError::SelfTestFailed { response }),
+        }
+        // The device ID is sent after both a passed and a failed test
+        let device_id = self.controller.read_data();
+        if response == SELF_TEST_FAILED {
+            return Err(MouseError::SelfTestFailed { response });
+        }
+        let mouse_type = MouseType::from(device_id?);
+        self.controller.set_mouse_type(mouse_type);
+        match mouse_type {
+            MouseType::Unknown(id) => Err(MouseError::UnexpectedDeviceId(id)),
+            mouse_type => Ok(mouse_type),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate std;
+
+    use super::*;
+    use crate::{
+        mock::{self, COMMAND, DATA},
+        COMMAND_ACKNOWLEDGED,
+    };
+    use std::vec::Vec;
+
+    #[test]
+    fn sign_extend_4bit_test() {
+        assert_eq!(sign_extend_4bit(0x07), 7);
+        assert_eq!(sign_extend_4bit(0x08), -8);
+        assert_eq!(sign_extend_4bit(0x0f), -1);
+        assert_eq!(sign_extend_4bit(0xf1), 1);
+    }
+
+    #[test]
+    fn is_present_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x00, 0x02, 100]);
+        let mut controller = unsafe { Controller::new() };
+        assert!(controller.mouse().is_present());
+        assert_eq!(mock::writes(), [(COMMAND, 0xd4), (DATA, 0xe9)]);
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn set_resolution_and_verify_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 3]);
+        mock::push_mouse_data(&[0x00, 0x02, 100]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .mouse()
+            .set_resolution_and_verify(MouseResolution::FourCountsPerMm)
+            .unwrap();
+        assert_eq!(
+            mock::writes(),
+            [
+                (COMMAND, 0xd4),
+                (DATA, 0xe8),
+                (COMMAND, 0xd4),
+                (DATA, 0x02),
+                (COMMAND, 0xd4),
+                (DATA, 0xe9)
+            ]
+        );
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 3]);
+        mock::push_mouse_data(&[0x00, 0x01, 100]);
+        assert!(matches!(
+            controller
+                .mouse()
+                .set_resolution_and_verify(MouseResolution::EightCountsPerMm),
+            Err(MouseError::InvalidResolution(1))
+        ));
+    }
+
+    #[test]
+    fn set_sample_rate_and_verify_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 3]);
+        mock::push_mouse_data(&[0x00, 0x02, 200]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .mouse()
+            .set_sample_rate_and_verify(MouseSampleRate::TwoHundredPerSecond)
+            .unwrap();
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 3]);
+        mock::push_mouse_data(&[0x00, 0x02, 100]);
+        assert!(matches!(
+            controller
+                .mouse()
+                .set_sample_rate_and_verify(MouseSampleRate::TwoHundredPerSecond),
+            Err(MouseError::InvalidSampleRate(100))
+        ));
+    }
+
+    fn mouse_commands() -> Vec<u8> {
+        let writes = mock::writes();
+        assert!(writes.chunks(2).all(|pair| pair[0] == (COMMAND, 0xd4)));
+        writes.chunks(2).map(|pair| pair[1].1).collect()
+    }
+
+    #[test]
+    fn configure_restores_reporting_test() {
+        mock::reset();
+        mock::push_mouse_data(&[0x08, 0x01]);
+        mock::push_reply(&[0x02, 0x08, COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .mouse()
+            .configure(true, |mouse| mouse.set_scaling_two_to_one())
+            .unwrap();
+        assert_eq!(mouse_commands(), [0xf5, 0xe7, 0xf4]);
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn configure_leaves_reporting_disabled_test() {
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[RESEND]);
+        let mut controller = unsafe { Controller::new() };
+        let result = controller
+            .mouse()
+            .configure(false, |mouse| mouse.set_scaling_two_to_one());
+        assert!(matches!(result, Err(MouseError::Resend)));
+        assert_eq!(mouse_commands(), [0xf5, 0xe7]);
+    }
+
+    #[test]
+    fn set_defaults_and_enable_reporting_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 2]);
+        let mut controller = unsafe { Controller::new() };
+        controller
+            .mouse()
+            .set_defaults_and_enable_reporting()
+            .unwrap();
+        assert_eq!(mouse_commands(), [0xf6, 0xf4]);
+    }
+
+    #[test]
+    fn set_mode_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 3]);
+        let mut controller = unsafe { Controller::new() };
+        for &mode in &[MouseMode::Remote, MouseMode::Stream, MouseMode::Wrap] {
+            controller.mouse().set_mode(mode).unwrap();
+        }
+        assert_eq!(mouse_commands(), [0xf0, 0xea, 0xee]);
+    }
+
+    #[test]
+    fn with_remote_mode_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 2]);
+        mock::push_mouse_data(&[0x08, 0x01, 0x02, COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        let (_, dx, dy) = controller
+            .mouse()
+            .with_remote_mode(|mouse| mouse.request_data_packet())
+            .unwrap();
+        assert_eq!((dx, dy), (1, 2));
+        assert_eq!(mouse_commands(), [0xf0, 0xeb, 0xea]);
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, RESEND, COMMAND_ACKNOWLEDGED]);
+        let result = controller
+            .mouse()
+            .with_remote_mode(|mouse| mouse.request_data_packet());
+        assert!(matches!(result, Err(MouseError::Resend)));
+        assert_eq!(mouse_commands(), [0xf0, 0xeb, 0xea]);
+    }
+
+    #[test]
+    fn mouse_packet_size_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.mouse_packet_size(), 3);
+
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 6]);
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x03]);
+        let mut mouse = controller.mouse();
+        mouse.send_sample_rate_sequence(&[200, 100, 80]).unwrap();
+        assert_eq!(mouse.get_mouse_type().unwrap(), MouseType::IntelliMouse);
+        assert_eq!(controller.mouse_packet_size(), 4);
+
+        mock::push_mouse_data(&[0x08, 0x01, 0x02, 0xff]);
+        let packet = controller.mouse().wait_for_data_packet().unwrap();
+        assert_eq!(packet.dz, Some(-1));
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn resync_test() {
+        mock::reset();
+        mock::push_mouse_data(&[0x01, 0x02]);
+        mock::push_reply(&[0x03, COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.mouse().resync(true).unwrap(), 3);
+        assert_eq!(mouse_commands(), [0xf5, 0xf4]);
+        assert_eq!(mock::pending(), 0);
+
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        assert_eq!(controller.mouse().resync(false).unwrap(), 0);
+        assert_eq!(mouse_commands(), [0xf5]);
+    }
+
+    #[test]
+    fn send_sample_rate_sequence_test() {
+        for &(mouse_type, rates) in &[
+            (MouseType::IntelliMouse, [200, 100, 80]),
+            (MouseType::IntelliMouseExplorer, [200, 200, 80]),
+        ] {
+            mock::reset();
+            mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED; 6]);
+            let mut controller = unsafe { Controller::new() };
+            let sequence = mouse_type.sample_rate_sequence().unwrap();
+            assert_eq!(sequence, rates);
+            controller
+                .mouse()
+                .send_sample_rate_sequence(&sequence)
+                .unwrap();
+            assert_eq!(
+                mock::writes()
+                    .iter()
+                    .filter(|&&(port, _)| port == DATA)
+                    .map(|&(_, byte)| byte)
+                    .collect::<Vec<_>>(),
+                [0xf3, rates[0], 0xf3, rates[1], 0xf3, rates[2]]
+            );
+        }
+
+        mock::reset();
+        let mut controller = unsafe { Controller::new() };
+        assert!(matches!(
+            controller.mouse().send_sample_rate_sequence(&[200, 90, 80]),
+            Err(MouseError::InvalidSampleRate(90))
+        ));
+        assert!(mock::writes().is_empty());
+    }
+
+    #[test]
+    fn reset_and_self_test_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, SELF_TEST_PASSED, 0x03]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller.mouse().reset_and_self_test().unwrap(),
+            MouseType::IntelliMouse
+        );
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, SELF_TEST_PASSED, 0x42]);
+        assert!(matches!(
+            controller.mouse().reset_and_self_test(),
+            Err(MouseError::UnexpectedDeviceId(0x42))
+        ));
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED]);
+        assert!(matches!(
+            controller.mouse().reset_and_self_test(),
+            Err(MouseError::SelfTestTimeout)
+        ));
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0xfc, 0x00]);
+        assert!(matches!(
+            controller.mouse().reset_and_self_test(),
+            Err(MouseError::SelfTestFailed { response: 0xfc })
+        ));
+        assert_eq!(mock::pending(), 0);
+
+        // No device ID follows other responses
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x37]);
+        assert!(matches!(
+            controller.mouse().reset_and_self_test(),
+            Err(MouseError::SelfTestFailed { response: 0x37 })
+        ));
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, RESEND]);
+        assert!(matches!(
+            controller.mouse().reset_and_self_test(),
+            Err(MouseError::Resend)
+        ));
+    }
+
+    #[test]
+    fn wait_for_data_packet_test() {
+        mock::reset();
+        mock::push_data(&[0x1c]);
+        mock::push_mouse_data(&[0x09, 0x01]);
+        mock::push_data(&[0xf0, 0x1c]);
+        mock::push_mouse_data(&[0x02]);
+        let mut controller = unsafe { Controller::new() };
+        let mut mouse = controller.mouse();
+        let packet = mouse.wait_for_data_packet().unwrap();
+        assert_eq!((packet.dx, packet.dy), (1, 2));
+        assert!(packet
+            .flags
+            .contains(MouseMovementFlags::LEFT_BUTTON_PRESSED));
+        assert_eq!(mouse.skipped_bytes(), 3);
+    }
+
+    fn push_resolution_probe_replies(reported: [u8; 4]) {
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED, 0x00, 0x02, 100]);
+        for &resolution in &reported {
+            mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+            mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+            mock::push_reply(&[COMMAND_ACKNOWLEDGED, 0x00, resolution, 100]);
+        }
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+    }
+
+    #[test]
+    fn test_resolution_support_test() {
+        mock::reset();
+        // The mouse clamps the highest resolution
+        push_resolution_probe_replies([0, 1, 2, 2]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller.mouse().test_resolution_support().unwrap(),
+            0b0111
+        );
+        // Original resolution is restored
+        assert_eq!(mouse_commands()[mouse_commands().len() - 2..], [0xe8, 0x02]);
+        assert_eq!(mock::pending(), 0);
+
+        mock::reset();
+        push_resolution_probe_replies([0, 1, 2, 2]);
+        assert_eq!(
+            controller.mouse().max_supported_resolution().unwrap(),
+            MouseResolution::FourCountsPerMm
+        );
+
+        // Original resolution is restored when probing fails
+        mock::reset();
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED, 0x00, 0x02, 100]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED, 0x80, 0x00, 100]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        mock::push_reply(&[COMMAND_ACKNOWLEDGED]);
+        assert!(matches!(
+            controller.mouse().test_resolution_support(),
+            Err(MouseError::InvalidStatusFlags(0x80))
+        ));
+        assert_eq!(mouse_commands(), [0xe9, 0xe8, 0x00, 0xe9, 0xe8, 0x02]);
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn request_packets_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x08, 0x01, 0x02]);
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x28, 0x03, 0xff]);
+        let mut controller = unsafe { Controller::new() };
+        let packets = controller.mouse().request_packets::<2>().unwrap();
+        assert_eq!((packets[0].dx, packets[0].dy), (1, 2));
+        assert_eq!((packets[1].dx, packets[1].dy), (3, -1));
+        assert_eq!(mouse_commands(), [0xeb, 0xeb]);
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x08, 0x01, 0x02, RESEND]);
+        assert!(matches!(
+            controller.mouse().request_packets::<3>(),
+            Err((1, MouseError::Resend))
+        ));
+    }
+
+    #[test]
+    fn read_packet_test() {
+        mock::reset();
+        mock::push_data(&[0x05, 0x00, 0x09, 0x01, 0x02]);
+        let mut controller = unsafe { Controller::new() };
+        let packet = controller
+            .mouse()
+            .read_packet(MousePacket::is_valid_first_byte)
+            .unwrap();
+        assert_eq!(
+            packet.flags,
+            MouseMovementFlags::ALWAYS_ONE | MouseMovementFlags::LEFT_BUTTON_PRESSED
+        );
+        assert_eq!((packet.dx, packet.dy, packet.dz), (1, 2, None));
+        assert_eq!(mock::pending(), 0);
+
+        mock::reset();
+        mock::push_data(&[0x00, 0x08, 0x01, 0x02, 0xff]);
+        controller.set_mouse_type(MouseType::IntelliMouse);
+        let packet = controller
+            .mouse()
+            .read_packet(MousePacket::is_valid_first_byte)
+            .unwrap();
+        assert_eq!((packet.dx, packet.dy, packet.dz), (1, 2, Some(-1)));
+
+        mock::reset();
+        mock::push_data(&[0x00; MAX_PACKET_RESYNC_DISCARDS + 1]);
+        assert!(matches!(
+            controller
+                .mouse()
+                .read_packet(MousePacket::is_valid_first_byte),
+            Err(MouseError::InvalidResponse(0x00))
+        ));
+        assert_eq!(mock::pending(), 1);
+    }
+
+    #[test]
+    fn read_data_packet_raw_test() {
+        mock::reset();
+        mock::push_mouse_data(&[0x29, 0x05, 0xfe]);
+        let mut controller = unsafe { Controller::new() };
+        let (bytes, len, (flags, dx, dy)) = controller.mouse().read_data_packet_raw().unwrap();
+        assert_eq!((bytes, len), ([0x29, 0x05, 0xfe, 0x00], 3));
+        assert_eq!(flags.bits(), bytes[0]);
+        assert_eq!((dx, dy), (5, -2));
+    }
+
+    #[test]
+    fn get_status_packet_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x20, 0x03, 40]);
+        let mut controller = unsafe { Controller::new() };
+        let (status, resolution, sample_rate) = controller.mouse().get_status_packet().unwrap();
+        assert_eq!(status, MouseStatusFlags::DATA_REPORTING_ENABLED);
+        assert_eq!(resolution, MouseResolution::EightCountsPerMm);
+        assert_eq!(sample_rate, 40);
+
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x20, 0x04, 40]);
+        assert!(matches!(
+            controller.mouse().get_status_packet(),
+            Err(MouseError::InvalidResolution(4))
+        ));
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn alternate_ack_test() {
+        mock::reset();
+        mock::push_mouse_data(&[0xfb]);
+        let mut controller = unsafe { Controller::new() };
+        controller.set_alternate_acks(&[0xfb]);
+        controller.mouse().set_defaults().unwrap();
+        assert_eq!(mouse_commands(), [0xf6]);
+    }
+
+    #[test]
+    fn get_mouse_id_raw_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x42]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(controller.mouse().get_mouse_id_raw().unwrap(), 0x42);
+        assert_eq!(mouse_commands(), [0xf2]);
+        assert_eq!(controller.mouse_packet_size(), 3);
+    }
+
+    #[test]
+    fn reset_counters_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0xff, 0x07, 50]);
+        let mut controller = unsafe { Controller::new() };
+        controller.mouse().reset_counters().unwrap();
+        assert_eq!(mouse_commands(), [0xe9]);
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn get_sample_rate_and_resolution_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x00, 0x01, 80]);
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x00, 0x01, 80]);
+        let mut controller = unsafe { Controller::new() };
+        assert_eq!(
+            controller.mouse().get_sample_rate().unwrap(),
+            MouseSampleRate::EightyPerSecond
+        );
+        assert_eq!(
+            controller.mouse().get_current_resolution().unwrap(),
+            MouseResolution::TwoCountsPerMm
+        );
+        assert_eq!(mouse_commands(), [0xe9, 0xe9]);
+    }
+
+    #[test]
+    fn get_status_packet_invalid_sample_rate_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x00, 0x02, 50]);
+        let mut controller = unsafe { Controller::new() };
+        assert!(matches!(
+            controller.mouse().get_status_packet(),
+            Err(MouseError::InvalidSampleRate(50))
+        ));
+        assert!(matches!(
+            controller.mouse().set_sample_rate(50),
+            Err(MouseError::InvalidSampleRate(50))
+        ));
+    }
+
+    #[test]
+    fn get_status_packet_reserved_bits_test() {
+        mock::reset();
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x28, 0x02, 100]);
+        let mut controller = unsafe { Controller::new() };
+        assert!(matches!(
+            controller.mouse().get_status_packet(),
+            Err(MouseError::InvalidStatusFlags(0x28))
+        ));
+        assert_eq!(mock::pending(), 0);
+    }
+
+    #[test]
+    fn is_not_present_test() {
+        mock::reset();
+        let mut controller = unsafe { Controller::with_timeout(1000) };
+        assert!(!controller.mouse().is_present());
+        assert!(mock::status_reads() < 1000);
+        assert_eq!(controller.timeout(), 1000);
+        assert_eq!(controller.mouse().detect_mouse_type().unwrap(), None);
+
+        mock::push_mouse_data(&[COMMAND_ACKNOWLEDGED, 0x04]);
+        assert_eq!(
+            controller.mouse().detect_mouse_type().unwrap(),
+            Some(MouseType::IntelliMouseExplorer)
+        );
     }
 }
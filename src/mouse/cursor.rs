@@ -0,0 +1,85 @@
+/// Tracks a cursor position by accumulating mouse movement, clamped to a bounding box.
+///
+/// Mice report upward movement as a positive `dy`, while most screens have a downward Y axis.
+/// Set `invert_y` to subtract `dy` from the position instead of adding it.
+///
+/// # Examples
+/// ```
+/// use ps2::Cursor;
+///
+/// let mut cursor = Cursor::new((0, 0), (640, 480), true);
+/// assert_eq!(cursor.position(), (320, 240));
+/// assert_eq!(cursor.apply(10, 20), (330, 220));
+/// assert_eq!(cursor.apply(-500, 0), (0, 220));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cursor {
+    position: (i32, i32),
+    min: (i32, i32),
+    max: (i32, i32),
+    invert_y: bool,
+}
+
+impl Cursor {
+    /// Create a cursor confined to the box between `min` and `max`, inclusive, starting at its
+    /// center. `min` must not be greater than `max` on either axis.
+    pub const fn new(min: (i32, i32), max: (i32, i32), invert_y: bool) -> Self {
+        Self {
+            position: (min.0 + (max.0 - min.0) / 2, min.1 + (max.1 - min.1) / 2),
+            min,
+            max,
+            invert_y,
+        }
+    }
+
+    /// The current position of the cursor.
+    pub const fn position(&self) -> (i32, i32) {
+        self.position
+    }
+
+    /// Move the cursor to the given position, clamped to the bounding box.
+    pub fn set_position(&mut self, x: i32, y: i32) {
+        self.position = (
+            x.clamp(self.min.0, self.max.0),
+            y.clamp(self.min.1, self.max.1),
+        );
+    }
+
+    /// Move the cursor by the movement from a packet, returning the new position.
+    pub fn apply(&mut self, dx: i16, dy: i16) -> (i32, i32) {
+        let dy = if self.invert_y {
+            -(dy as i32)
+        } else {
+            dy as i32
+        };
+        self.set_position(
+            self.position.0.saturating_add(dx as i32),
+            self.position.1.saturating_add(dy),
+        );
+        self.position
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clamps_to_edges_test() {
+        let mut cursor = Cursor::new((-10, -10), (10, 10), false);
+        assert_eq!(cursor.position(), (0, 0));
+        assert_eq!(cursor.apply(-100, 0), (-10, 0));
+        assert_eq!(cursor.apply(100, 0), (10, 0));
+        assert_eq!(cursor.apply(0, -100), (10, -10));
+        assert_eq!(cursor.apply(0, 100), (10, 10));
+        assert_eq!(cursor.apply(-5, -5), (5, 5));
+    }
+
+    #[test]
+    fn invert_y_test() {
+        let mut cursor = Cursor::new((0, 0), (100, 100), true);
+        assert_eq!(cursor.apply(0, 20), (50, 30));
+        assert_eq!(cursor.apply(0, -100), (50, 100));
+        assert_eq!(cursor.apply(0, 200), (50, 0));
+    }
+}
@@ -0,0 +1,10 @@
+/// PS/2 mouse operating mode. For more details, see [here](https://web.archive.org/web/20090325002201/http://www.computer-engineering.org/index.php?title=PS/2_Mouse_Interface#Modes_of_Operation).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MouseMode {
+    /// The mouse sends movement packets whenever it moves, if data reporting is enabled.
+    Stream,
+    /// The mouse only sends movement packets when requested.
+    Remote,
+    /// The mouse echoes back every byte it receives, other than a few commands.
+    Wrap,
+}
@@ -0,0 +1,35 @@
+use core::convert::TryFrom;
+
+use crate::error::MouseError;
+
+/// PS/2 mouse resolution, in counts per millimeter of movement. For more details, see
+/// [here](https://web.archive.org/web/20090325002201/http://www.computer-engineering.org/index.php?title=PS/2_Mouse_Interface#Inputs.2C_Resolution.2C_and_Scaling).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MouseResolution {
+    OneCountPerMm = 0,
+    TwoCountsPerMm = 1,
+    FourCountsPerMm = 2,
+    EightCountsPerMm = 3,
+}
+
+impl MouseResolution {
+    /// The number of counts reported per millimeter of movement.
+    pub const fn counts_per_mm(self) -> u8 {
+        1 << self as u8
+    }
+}
+
+impl TryFrom<u8> for MouseResolution {
+    type Error = MouseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(MouseResolution::OneCountPerMm),
+            1 => Ok(MouseResolution::TwoCountsPerMm),
+            2 => Ok(MouseResolution::FourCountsPerMm),
+            3 => Ok(MouseResolution::EightCountsPerMm),
+            other => Err(MouseError::InvalidResolution(other)),
+        }
+    }
+}
@@ -0,0 +1,33 @@
+use core::convert::TryFrom;
+
+use crate::error::MouseError;
+
+/// PS/2 mouse sample rate, in samples per second. Each variant's discriminant is its rate.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum MouseSampleRate {
+    TenPerSecond = 10,
+    TwentyPerSecond = 20,
+    FortyPerSecond = 40,
+    SixtyPerSecond = 60,
+    EightyPerSecond = 80,
+    OneHundredPerSecond = 100,
+    TwoHundredPerSecond = 200,
+}
+
+impl TryFrom<u8> for MouseSampleRate {
+    type Error = MouseError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            10 => Ok(MouseSampleRate::TenPerSecond),
+            20 => Ok(MouseSampleRate::TwentyPerSecond),
+            40 => Ok(MouseSampleRate::FortyPerSecond),
+            60 => Ok(MouseSampleRate::SixtyPerSecond),
+            80 => Ok(MouseSampleRate::EightyPerSecond),
+            100 => Ok(MouseSampleRate::OneHundredPerSecond),
+            200 => Ok(MouseSampleRate::TwoHundredPerSecond),
+            other => Err(MouseError::InvalidSampleRate(other)),
+        }
+    }
+}
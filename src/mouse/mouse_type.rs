@@ -1,5 +1,5 @@
 /// PS/2 mouse device type. For more details, see [here](https://web.archive.org/web/20200616182210/https://www.win.tue.nl/%7Eaeb/linux/kbd/scancodes-13.html#ss13.3).
-#[derive(Debug, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum MouseType {
     Standard,
     IntelliMouse,
@@ -8,6 +8,29 @@ pub enum MouseType {
     Unknown(u8),
 }
 
+impl MouseType {
+    /// The number of bytes in each movement data packet sent by this type of mouse.
+    pub const fn packet_size(&self) -> usize {
+        match self {
+            MouseType::IntelliMouse | MouseType::IntelliMouseExplorer => 4,
+            _ => 3,
+        }
+    }
+
+    /// The sequence of sample rates that switches a mouse into this mode, if it supports it. Use
+    /// [`Mouse::send_sample_rate_sequence`](crate::Mouse::send_sample_rate_sequence) to send it,
+    /// then check the new mode using [`Mouse::get_mouse_type`](crate::Mouse::get_mouse_type).
+    ///
+    /// An IntelliMouse Explorer must be switched into IntelliMouse mode first.
+    pub const fn sample_rate_sequence(&self) -> Option<[u8; 3]> {
+        match self {
+            MouseType::IntelliMouse => Some([200, 100, 80]),
+            MouseType::IntelliMouseExplorer => Some([200, 200, 80]),
+            _ => None,
+        }
+    }
+}
+
 impl From<u8> for MouseType {
     fn from(value: u8) -> Self {
         match value {
@@ -0,0 +1,405 @@
+use core::sync::atomic::{AtomicBool, AtomicI16, AtomicI8, AtomicU8, AtomicUsize, Ordering};
+
+use crate::{
+    flags::MouseMovementFlags,
+    mouse::{sign_extend_4bit, MouseType},
+};
+
+/// A movement data packet sent by the mouse.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MousePacket {
+    /// The button states and sign/overflow bits from the first byte of the packet.
+    pub flags: MouseMovementFlags,
+    /// Horizontal movement since the last packet was sent.
+    pub dx: i16,
+    /// Vertical movement since the last packet was sent.
+    pub dy: i16,
+    /// Scroll wheel movement since the last packet was sent, for mice that send 4-byte packets.
+    ///
+    /// The plain IntelliMouse uses the whole fourth byte, while the IntelliMouse Explorer only
+    /// uses its low 4 bits, giving a range of `-8..=7`. On Explorer mice with a tilt wheel,
+    /// horizontal scrolling is reported as a movement of `-2` or `2`.
+    pub dz: Option<i8>,
+    /// Whether the fourth button is pressed. Only reported by the IntelliMouse Explorer.
+    pub button4: bool,
+    /// Whether the fifth button is pressed. Only reported by the IntelliMouse Explorer.
+    pub button5: bool,
+}
+
+impl MousePacket {
+    /// Whether a byte can be the first byte of a packet, which always has
+    /// [`MouseMovementFlags::ALWAYS_ONE`] set. This is the same for all mouse types.
+    pub const fn is_valid_first_byte(byte: u8) -> bool {
+        MouseMovementFlags::from_bits_truncate(byte).contains(MouseMovementFlags::ALWAYS_ONE)
+    }
+
+    /// Decode a packet from its raw bytes. Must be given at least 3 bytes, and a fourth is only
+    /// used if the mouse type sends 4-byte packets.
+    pub(crate) fn from_bytes(bytes: &[u8], mouse_type: MouseType) -> Self {
+        let flags = MouseMovementFlags::from_bits_truncate(bytes[0]);
+        let mut dx = bytes[1] as u16;
+        let mut dy = bytes[2] as u16;
+
+        // Movement values are 9-bit two's complement integers, with the sign bit in the first byte
+        if flags.contains(MouseMovementFlags::X_SIGN_BIT) {
+            dx |= 0xff00;
+        }
+        if flags.contains(MouseMovementFlags::Y_SIGN_BIT) {
+            dy |= 0xff00;
+        }
+
+        let (dz, button4, button5) = match (mouse_type, bytes.get(3)) {
+            (MouseType::IntelliMouse, Some(&z)) => (Some(z as i8), false, false),
+            (MouseType::IntelliMouseExplorer, Some(&z)) => (
+                Some(sign_extend_4bit(z)),
+                z & 0b0001_0000 != 0,
+                z & 0b0010_0000 != 0,
+            ),
+            _ => (None, false, false),
+        };
+
+        Self {
+            flags,
+            dx: dx as i16,
+            dy: dy as i16,
+            dz,
+            button4,
+            button5,
+        }
+    }
+}
+
+/// Assembles movement data packets from bytes received one at a time.
+///
+/// This is intended for interrupt handlers, which receive a single byte from the mouse on each
+/// interrupt. The assembler knows how long each packet is from the [`MouseType`] it was created
+/// with. If a byte is lost or a spurious byte is received, the assembler resynchronizes by
+/// discarding bytes until it sees one with [`MouseMovementFlags::ALWAYS_ONE`] set, which every
+/// packet starts with.
+///
+/// # Examples
+/// ```
+/// use ps2::{MouseType, PacketAssembler};
+///
+/// let mut assembler = PacketAssembler::new(MouseType::Standard);
+/// assert!(assembler.push(0x08).is_none());
+/// assert!(assembler.push(0x01).is_none());
+/// let packet = assembler.push(0x02).unwrap();
+/// assert_eq!((packet.dx, packet.dy), (1, 2));
+/// ```
+#[derive(Debug)]
+pub struct PacketAssembler {
+    mouse_type: MouseType,
+    buffer: [u8; 4],
+    len: usize,
+}
+
+impl PacketAssembler {
+    /// Create an assembler for packets sent by the given type of mouse.
+    pub const fn new(mouse_type: MouseType) -> Self {
+        Self {
+            mouse_type,
+            buffer: [0; 4],
+            len: 0,
+        }
+    }
+
+    /// The number of bytes in each packet.
+    pub const fn packet_size(&self) -> usize {
+        self.mouse_type.packet_size()
+    }
+
+    /// Add a byte received from the mouse, returning a packet if it was the last byte of one.
+    pub fn push(&mut self, byte: u8) -> Option<MousePacket> {
+        if self.len == 0 && !MousePacket::is_valid_first_byte(byte) {
+            return None;
+        }
+
+        self.buffer[self.len] = byte;
+        self.len += 1;
+        if self.len < self.packet_size() {
+            return None;
+        }
+
+        self.len = 0;
+        Some(MousePacket::from_bytes(&self.buffer, self.mouse_type))
+    }
+
+    /// Discard any partially received packet.
+    pub fn reset(&mut self) {
+        self.len = 0;
+    }
+}
+
+/// Assembles movement data packets from bytes received in an interrupt handler, for use in the
+/// main context.
+///
+/// Unlike [`PacketAssembler`], all methods take `&self` and only use atomic operations, so a single
+/// assembler can be stored in a `static` and shared between the IRQ12 handler and the rest of the
+/// kernel on a single-core system. The handler calls [`MousePacketAssembler::push_from_irq`] with
+/// each byte, and the main context calls [`MousePacketAssembler::take_packet`] to collect complete
+/// packets. If more packets are completed before one is taken, they are merged into a single
+/// packet so that no movement is lost: the movement is summed, saturating at the limits of the
+/// packet fields, the overflow bits are combined, and the button states are taken from the latest
+/// packet.
+///
+/// # Examples
+/// ```
+/// use ps2::{MousePacketAssembler, MouseType};
+///
+/// static ASSEMBLER: MousePacketAssembler = MousePacketAssembler::new(MouseType::Standard);
+///
+/// // In the interrupt handler
+/// for &byte in &[0x08, 0x01, 0x02] {
+///     ASSEMBLER.push_from_irq(byte);
+/// }
+///
+/// // In the main context
+/// let packet = ASSEMBLER.take_packet().unwrap();
+/// assert_eq!((packet.dx, packet.dy), (1, 2));
+/// ```
+#[derive(Debug)]
+pub struct MousePacketAssembler {
+    mouse_type: MouseType,
+    partial: [AtomicU8; 4],
+    len: AtomicUsize,
+    flags: AtomicU8,
+    extra_buttons: AtomicU8,
+    dx: AtomicI16,
+    dy: AtomicI16,
+    dz: AtomicI8,
+    ready: AtomicBool,
+}
+
+impl MousePacketAssembler {
+    const OVERFLOW_BITS: u8 = MouseMovementFlags::X_OVERFLOW
+        .union(MouseMovementFlags::Y_OVERFLOW)
+        .bits();
+    const BUTTON4: u8 = 0b01;
+    const BUTTON5: u8 = 0b10;
+
+    /// Create an assembler for packets sent by the given type of mouse.
+    pub const fn new(mouse_type: MouseType) -> Self {
+        Self {
+            mouse_type,
+            partial: [
+                AtomicU8::new(0),
+                AtomicU8::new(0),
+                AtomicU8::new(0),
+                AtomicU8::new(0),
+            ],
+            len: AtomicUsize::new(0),
+            flags: AtomicU8::new(0),
+            extra_buttons: AtomicU8::new(0),
+            dx: AtomicI16::new(0),
+            dy: AtomicI16::new(0),
+            dz: AtomicI8::new(0),
+            ready: AtomicBool::new(false),
+        }
+    }
+
+    /// Add a byte received from the mouse. This never blocks or panics, so it's safe to call from
+    /// an interrupt handler.
+    ///
+    /// Like [`PacketAssembler::push`], bytes are discarded until one that can start a packet is
+    /// received.
+    pub fn push_from_irq(&self, byte: u8) {
+        let len = self.len.load(Ordering::Relaxed);
+        if len == 0 && !MousePacket::is_valid_first_byte(byte) {
+            return;
+        }
+
+        if let Some(slot) = self.partial.get(len) {
+            slot.store(byte, Ordering::Relaxed);
+        }
+        let len = len + 1;
+        if len < self.mouse_type.packet_size() {
+            self.len.store(len, Ordering::Relaxed);
+            return;
+        }
+
+        self.len.store(0, Ordering::Relaxed);
+        let mut bytes = [0; 4];
+        for (byte, partial) in bytes.iter_mut().zip(self.partial.iter()) {
+            *byte = partial.load(Ordering::Relaxed);
+        }
+        let packet = MousePacket::from_bytes(&bytes[..len], self.mouse_type);
+
+        // Merge into any packet that hasn't been taken yet. The main context only resets the
+        // movement and overflow bits, so it can't lose anything added here.
+        let flags = packet.flags.bits();
+        self.flags.fetch_and(Self::OVERFLOW_BITS, Ordering::Relaxed);
+        self.flags.fetch_or(
+            flags & (Self::OVERFLOW_BITS | MouseMovementFlags::BUTTONS.bits()),
+            Ordering::Relaxed,
+        );
+        let mut extra_buttons = 0;
+        if packet.button4 {
+            extra_buttons |= Self::BUTTON4;
+        }
+        if packet.button5 {
+            extra_buttons |= Self::BUTTON5;
+        }
+        self.extra_buttons.store(extra_buttons, Ordering::Relaxed);
+        let _ = self
+            .dx
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dx| {
+                Some(dx.saturating_add(packet.dx))
+            });
+        let _ = self
+            .dy
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dy| {
+                Some(dy.saturating_add(packet.dy))
+            });
+        let _ = self
+            .dz
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |dz| {
+                Some(dz.saturating_add(packet.dz.unwrap_or(0)))
+            });
+        self.ready.store(true, Ordering::Release);
+    }
+
+    /// Take the packet completed since the last one was taken, if there is one. If several
+    /// packets were completed, they are merged.
+    pub fn take_packet(&self) -> Option<MousePacket> {
+        if !self.ready.swap(false, Ordering::Acquire) {
+            return None;
+        }
+        let dx = self.dx.swap(0, Ordering::Relaxed);
+        let dy = self.dy.swap(0, Ordering::Relaxed);
+        let dz = self.dz.swap(0, Ordering::Relaxed);
+        // The button states stay in place, in case a packet arrives before the next take
+        let mut flags = MouseMovementFlags::from_bits_truncate(
+            self.flags
+                .fetch_and(!Self::OVERFLOW_BITS, Ordering::Relaxed),
+        ) | MouseMovementFlags::ALWAYS_ONE;
+        flags.set(MouseMovementFlags::X_SIGN_BIT, dx < 0);
+        flags.set(MouseMovementFlags::Y_SIGN_BIT, dy < 0);
+        let extra_buttons = self.extra_buttons.load(Ordering::Relaxed);
+        let dz = match self.mouse_type {
+            MouseType::IntelliMouse | MouseType::IntelliMouseExplorer => Some(dz),
+            _ => None,
+        };
+        Some(MousePacket {
+            flags,
+            dx,
+            dy,
+            dz,
+            button4: extra_buttons & Self::BUTTON4 != 0,
+            button5: extra_buttons & Self::BUTTON5 != 0,
+        })
+    }
+
+    /// Discard any partially received packet, so the next byte that can start a packet does.
+    pub fn reset_alignment(&self) {
+        self.len.store(0, Ordering::Relaxed);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn assembles_standard_packets_test() {
+        let mut assembler = PacketAssembler::new(MouseType::Standard);
+        // A spurious byte is injected between the two packets
+        let stream = [0x09, 0x01, 0x02, 0x00, 0x28, 0xff, 0x05];
+        let packets: [Option<MousePacket>; 7] = stream.map(|byte| assembler.push(byte));
+
+        assert_eq!(packets.iter().flatten().count(), 2);
+        assert_eq!(
+            packets[2],
+            Some(MousePacket {
+                flags: MouseMovementFlags::ALWAYS_ONE | MouseMovementFlags::LEFT_BUTTON_PRESSED,
+                dx: 1,
+                dy: 2,
+                dz: None,
+                button4: false,
+                button5: false,
+            })
+        );
+        assert_eq!(
+            packets[6],
+            Some(MousePacket {
+                flags: MouseMovementFlags::ALWAYS_ONE | MouseMovementFlags::Y_SIGN_BIT,
+                dx: 255,
+                dy: -251,
+                dz: None,
+                button4: false,
+                button5: false,
+            })
+        );
+    }
+
+    #[test]
+    fn assembles_intellimouse_packets_test() {
+        let mut assembler = PacketAssembler::new(MouseType::IntelliMouse);
+        assert_eq!(assembler.packet_size(), 4);
+        assert!(assembler.push(0x18).is_none());
+        assert!(assembler.push(0xfe).is_none());
+        assert!(assembler.push(0x00).is_none());
+        let packet = assembler.push(0xff).unwrap();
+        assert_eq!((packet.dx, packet.dy, packet.dz), (-2, 0, Some(-1)));
+    }
+
+    #[test]
+    fn decodes_fourth_byte_by_mouse_type_test() {
+        let bytes = [0x08, 0x00, 0x00, 0x2e];
+
+        let packet = MousePacket::from_bytes(&bytes, MouseType::IntelliMouse);
+        assert_eq!(
+            (packet.dz, packet.button4, packet.button5),
+            (Some(46), false, false)
+        );
+
+        let packet = MousePacket::from_bytes(&bytes, MouseType::IntelliMouseExplorer);
+        assert_eq!(
+            (packet.dz, packet.button4, packet.button5),
+            (Some(-2), false, true)
+        );
+
+        let packet =
+            MousePacket::from_bytes(&[0x08, 0x00, 0x00, 0x17], MouseType::IntelliMouseExplorer);
+        assert_eq!(
+            (packet.dz, packet.button4, packet.button5),
+            (Some(7), true, false)
+        );
+
+        let packet = MousePacket::from_bytes(&bytes, MouseType::Standard);
+        assert_eq!(
+            (packet.dz, packet.button4, packet.button5),
+            (None, false, false)
+        );
+    }
+
+    #[test]
+    fn interrupt_safe_assembler_test() {
+        let assembler = MousePacketAssembler::new(MouseType::IntelliMouse);
+        assert!(assembler.take_packet().is_none());
+
+        // Leading bytes are discarded, and the second packet is merged into the pending first one
+        for &byte in &[0x01, 0x02, 0x08, 0x01, 0x02, 0x03, 0x19, 0xfe, 0x00, 0xff] {
+            assembler.push_from_irq(byte);
+        }
+        let packet = assembler.take_packet().unwrap();
+        assert_eq!((packet.dx, packet.dy, packet.dz), (-1, 2, Some(2)));
+        assert_eq!(
+            packet.flags,
+            MouseMovementFlags::ALWAYS_ONE
+                | MouseMovementFlags::LEFT_BUTTON_PRESSED
+                | MouseMovementFlags::X_SIGN_BIT
+        );
+        assert!(assembler.take_packet().is_none());
+
+        assembler.push_from_irq(0x08);
+        assembler.reset_alignment();
+        for &byte in &[0x09, 0x00, 0x00, 0x00] {
+            assembler.push_from_irq(byte);
+        }
+        let packet = assembler.take_packet().unwrap();
+        assert!(packet
+            .flags
+            .contains(MouseMovementFlags::LEFT_BUTTON_PRESSED));
+    }
+}
@@ -0,0 +1,59 @@
+use crate::mouse::MouseResolution;
+
+/// Converts movement counts reported by the mouse to pixels, based on the mouse resolution.
+///
+/// The scale is given in pixels per millimeter of movement as an unsigned Q8.8 fixed-point
+/// number: the value divided by 256. For example, `0x0100` is one pixel per millimeter and
+/// `0x0280` is two and a half. This avoids floating point, which is often unavailable in kernels.
+///
+/// # Examples
+/// ```
+/// use ps2::{MouseResolution, MouseScaler};
+///
+/// // Two pixels per millimeter
+/// let scaler = MouseScaler::new(MouseResolution::FourCountsPerMm, 0x0200);
+/// assert_eq!(scaler.scale(8), 4);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MouseScaler {
+    counts_per_mm: u8,
+    pixels_per_mm: u16,
+}
+
+impl MouseScaler {
+    /// Create a scaler for a mouse with the given resolution, with a scale of `pixels_per_mm` in
+    /// Q8.8 fixed-point format.
+    pub const fn new(resolution: MouseResolution, pixels_per_mm: u16) -> Self {
+        Self {
+            counts_per_mm: resolution.counts_per_mm(),
+            pixels_per_mm,
+        }
+    }
+
+    /// Convert a movement in counts to a movement in pixels. The result is rounded toward zero,
+    /// so movements smaller than one pixel are lost.
+    pub const fn scale(&self, delta: i16) -> i32 {
+        // At most 2^15 * (2^16 - 1), which fits in an i32
+        delta as i32 * self.pixels_per_mm as i32 / (self.counts_per_mm as i32 * 256)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn scale_test() {
+        let scaler = MouseScaler::new(MouseResolution::FourCountsPerMm, 0x0100);
+        assert_eq!(scaler.scale(4), 1);
+        assert_eq!(scaler.scale(-8), -2);
+        assert_eq!(scaler.scale(3), 0);
+
+        let scaler = MouseScaler::new(MouseResolution::FourCountsPerMm, 0x0280);
+        assert_eq!(scaler.scale(8), 5);
+        assert_eq!(scaler.scale(-255), -159);
+
+        let scaler = MouseScaler::new(MouseResolution::OneCountPerMm, u16::MAX);
+        assert_eq!(scaler.scale(i16::MIN), -8_388_480);
+    }
+}
@@ -0,0 +1,19 @@
+/// Marker type for a [`Controller`](crate::Controller) with a keyboard attached to its first port.
+#[derive(Debug)]
+pub struct HasKeyboard;
+
+/// Marker type for a [`Controller`](crate::Controller) known to have no keyboard attached.
+///
+/// Controllers with this marker don't provide [`Controller::keyboard`](crate::Controller).
+#[derive(Debug)]
+pub struct NoKeyboard;
+
+/// Marker type for a [`Controller`](crate::Controller) with a mouse attached to its second port.
+#[derive(Debug)]
+pub struct HasMouse;
+
+/// Marker type for a [`Controller`](crate::Controller) known to have no mouse attached.
+///
+/// Controllers with this marker don't provide [`Controller::mouse`](crate::Controller).
+#[derive(Debug)]
+pub struct NoMouse;
@@ -0,0 +1,15 @@
+//! Scancodes for the keys of a 104-key US keyboard, in scancode sets 1 and 2.
+//!
+//! Each set module contains the make codes of the keys that are sent as a single byte, and an
+//! `extended` submodule for keys whose make code is prefixed with [`EXTENDED_PREFIX`]. Print Screen
+//! and Pause send longer sequences, which are provided in full.
+
+pub use self::translator::ScancodeTranslator;
+
+/// The byte sent before the make or break code of an extended key, in both scancode sets.
+pub const EXTENDED_PREFIX: u8 = 0xe0;
+
+pub mod set1;
+pub mod set2;
+
+mod translator;
@@ -0,0 +1,143 @@
+//! Scancode set 1, the set used by the original IBM PC/XT keyboard. This is also the set produced
+//! by the controller when scancode translation is enabled.
+//!
+//! The break code of a key is its make code with the high bit set. See [`break_code`].
+
+/// The bit that is set in the break code of a key.
+pub const BREAK_BIT: u8 = 0x80;
+
+/// Get the break code of a key, given its make code.
+pub const fn break_code(make_code: u8) -> u8 {
+    make_code | BREAK_BIT
+}
+
+pub const ESCAPE: u8 = 0x01;
+pub const KEY_1: u8 = 0x02;
+pub const KEY_2: u8 = 0x03;
+pub const KEY_3: u8 = 0x04;
+pub const KEY_4: u8 = 0x05;
+pub const KEY_5: u8 = 0x06;
+pub const KEY_6: u8 = 0x07;
+pub const KEY_7: u8 = 0x08;
+pub const KEY_8: u8 = 0x09;
+pub const KEY_9: u8 = 0x0a;
+pub const KEY_0: u8 = 0x0b;
+pub const MINUS: u8 = 0x0c;
+pub const EQUALS: u8 = 0x0d;
+pub const BACKSPACE: u8 = 0x0e;
+pub const TAB: u8 = 0x0f;
+pub const Q: u8 = 0x10;
+pub const W: u8 = 0x11;
+pub const E: u8 = 0x12;
+pub const R: u8 = 0x13;
+pub const T: u8 = 0x14;
+pub const Y: u8 = 0x15;
+pub const U: u8 = 0x16;
+pub const I: u8 = 0x17;
+pub const O: u8 = 0x18;
+pub const P: u8 = 0x19;
+pub const LEFT_BRACKET: u8 = 0x1a;
+pub const RIGHT_BRACKET: u8 = 0x1b;
+pub const ENTER: u8 = 0x1c;
+pub const LEFT_CONTROL: u8 = 0x1d;
+pub const A: u8 = 0x1e;
+pub const S: u8 = 0x1f;
+pub const D: u8 = 0x20;
+pub const F: u8 = 0x21;
+pub const G: u8 = 0x22;
+pub const H: u8 = 0x23;
+pub const J: u8 = 0x24;
+pub const K: u8 = 0x25;
+pub const L: u8 = 0x26;
+pub const SEMICOLON: u8 = 0x27;
+pub const APOSTROPHE: u8 = 0x28;
+pub const BACKTICK: u8 = 0x29;
+pub const LEFT_SHIFT: u8 = 0x2a;
+pub const BACKSLASH: u8 = 0x2b;
+pub const Z: u8 = 0x2c;
+pub const X: u8 = 0x2d;
+pub const C: u8 = 0x2e;
+pub const V: u8 = 0x2f;
+pub const B: u8 = 0x30;
+pub const N: u8 = 0x31;
+pub const M: u8 = 0x32;
+pub const COMMA: u8 = 0x33;
+pub const PERIOD: u8 = 0x34;
+pub const SLASH: u8 = 0x35;
+pub const RIGHT_SHIFT: u8 = 0x36;
+pub const NUMPAD_ASTERISK: u8 = 0x37;
+pub const LEFT_ALT: u8 = 0x38;
+pub const SPACE: u8 = 0x39;
+pub const CAPS_LOCK: u8 = 0x3a;
+pub const F1: u8 = 0x3b;
+pub const F2: u8 = 0x3c;
+pub const F3: u8 = 0x3d;
+pub const F4: u8 = 0x3e;
+pub const F5: u8 = 0x3f;
+pub const F6: u8 = 0x40;
+pub const F7: u8 = 0x41;
+pub const F8: u8 = 0x42;
+pub const F9: u8 = 0x43;
+pub const F10: u8 = 0x44;
+pub const NUM_LOCK: u8 = 0x45;
+pub const SCROLL_LOCK: u8 = 0x46;
+pub const NUMPAD_7: u8 = 0x47;
+pub const NUMPAD_8: u8 = 0x48;
+pub const NUMPAD_9: u8 = 0x49;
+pub const NUMPAD_MINUS: u8 = 0x4a;
+pub const NUMPAD_4: u8 = 0x4b;
+pub const NUMPAD_5: u8 = 0x4c;
+pub const NUMPAD_6: u8 = 0x4d;
+pub const NUMPAD_PLUS: u8 = 0x4e;
+pub const NUMPAD_1: u8 = 0x4f;
+pub const NUMPAD_2: u8 = 0x50;
+pub const NUMPAD_3: u8 = 0x51;
+pub const NUMPAD_0: u8 = 0x52;
+pub const NUMPAD_PERIOD: u8 = 0x53;
+pub const F11: u8 = 0x57;
+pub const F12: u8 = 0x58;
+
+/// The make code sequence sent when Print Screen is pressed.
+pub const PRINT_SCREEN_PRESSED: [u8; 4] = [0xe0, 0x2a, 0xe0, 0x37];
+/// The break code sequence sent when Print Screen is released.
+pub const PRINT_SCREEN_RELEASED: [u8; 4] = [0xe0, 0xb7, 0xe0, 0xaa];
+/// The sequence sent when Pause is pressed. Pause sends no break code when released.
+pub const PAUSE_PRESSED: [u8; 6] = [0xe1, 0x1d, 0x45, 0xe1, 0x9d, 0xc5];
+
+/// Make codes of extended keys, which are sent after
+/// [`EXTENDED_PREFIX`](crate::scancodes::EXTENDED_PREFIX). Break codes are sent after the same
+/// prefix.
+pub mod extended {
+    pub const NUMPAD_ENTER: u8 = 0x1c;
+    pub const RIGHT_CONTROL: u8 = 0x1d;
+    pub const NUMPAD_SLASH: u8 = 0x35;
+    pub const RIGHT_ALT: u8 = 0x38;
+    pub const HOME: u8 = 0x47;
+    pub const UP: u8 = 0x48;
+    pub const PAGE_UP: u8 = 0x49;
+    pub const LEFT: u8 = 0x4b;
+    pub const RIGHT: u8 = 0x4d;
+    pub const END: u8 = 0x4f;
+    pub const DOWN: u8 = 0x50;
+    pub const PAGE_DOWN: u8 = 0x51;
+    pub const INSERT: u8 = 0x52;
+    pub const DELETE: u8 = 0x53;
+    pub const LEFT_GUI: u8 = 0x5b;
+    pub const RIGHT_GUI: u8 = 0x5c;
+    pub const APPS: u8 = 0x5d;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn break_code_test() {
+        assert_eq!(break_code(A), 0x9e);
+        assert_eq!(break_code(extended::RIGHT_CONTROL), 0x9d);
+        assert_eq!(
+            PRINT_SCREEN_RELEASED[1],
+            break_code(PRINT_SCREEN_PRESSED[3])
+        );
+    }
+}
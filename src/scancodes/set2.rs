@@ -0,0 +1,123 @@
+//! Scancode set 2, the default set used by modern keyboards.
+//!
+//! The break code of a key is its make code prefixed with [`BREAK_PREFIX`]. For extended keys,
+//! [`EXTENDED_PREFIX`](crate::scancodes::EXTENDED_PREFIX) comes first, followed by
+//! [`BREAK_PREFIX`] and the make code.
+
+/// The byte sent before the make code of a key to indicate that it was released.
+pub const BREAK_PREFIX: u8 = 0xf0;
+
+pub const ESCAPE: u8 = 0x76;
+pub const KEY_1: u8 = 0x16;
+pub const KEY_2: u8 = 0x1e;
+pub const KEY_3: u8 = 0x26;
+pub const KEY_4: u8 = 0x25;
+pub const KEY_5: u8 = 0x2e;
+pub const KEY_6: u8 = 0x36;
+pub const KEY_7: u8 = 0x3d;
+pub const KEY_8: u8 = 0x3e;
+pub const KEY_9: u8 = 0x46;
+pub const KEY_0: u8 = 0x45;
+pub const MINUS: u8 = 0x4e;
+pub const EQUALS: u8 = 0x55;
+pub const BACKSPACE: u8 = 0x66;
+pub const TAB: u8 = 0x0d;
+pub const Q: u8 = 0x15;
+pub const W: u8 = 0x1d;
+pub const E: u8 = 0x24;
+pub const R: u8 = 0x2d;
+pub const T: u8 = 0x2c;
+pub const Y: u8 = 0x35;
+pub const U: u8 = 0x3c;
+pub const I: u8 = 0x43;
+pub const O: u8 = 0x44;
+pub const P: u8 = 0x4d;
+pub const LEFT_BRACKET: u8 = 0x54;
+pub const RIGHT_BRACKET: u8 = 0x5b;
+pub const ENTER: u8 = 0x5a;
+pub const LEFT_CONTROL: u8 = 0x14;
+pub const A: u8 = 0x1c;
+pub const S: u8 = 0x1b;
+pub const D: u8 = 0x23;
+pub const F: u8 = 0x2b;
+pub const G: u8 = 0x34;
+pub const H: u8 = 0x33;
+pub const J: u8 = 0x3b;
+pub const K: u8 = 0x42;
+pub const L: u8 = 0x4b;
+pub const SEMICOLON: u8 = 0x4c;
+pub const APOSTROPHE: u8 = 0x52;
+pub const BACKTICK: u8 = 0x0e;
+pub const LEFT_SHIFT: u8 = 0x12;
+pub const BACKSLASH: u8 = 0x5d;
+pub const Z: u8 = 0x1a;
+pub const X: u8 = 0x22;
+pub const C: u8 = 0x21;
+pub const V: u8 = 0x2a;
+pub const B: u8 = 0x32;
+pub const N: u8 = 0x31;
+pub const M: u8 = 0x3a;
+pub const COMMA: u8 = 0x41;
+pub const PERIOD: u8 = 0x49;
+pub const SLASH: u8 = 0x4a;
+pub const RIGHT_SHIFT: u8 = 0x59;
+pub const NUMPAD_ASTERISK: u8 = 0x7c;
+pub const LEFT_ALT: u8 = 0x11;
+pub const SPACE: u8 = 0x29;
+pub const CAPS_LOCK: u8 = 0x58;
+pub const F1: u8 = 0x05;
+pub const F2: u8 = 0x06;
+pub const F3: u8 = 0x04;
+pub const F4: u8 = 0x0c;
+pub const F5: u8 = 0x03;
+pub const F6: u8 = 0x0b;
+pub const F7: u8 = 0x83;
+pub const F8: u8 = 0x0a;
+pub const F9: u8 = 0x01;
+pub const F10: u8 = 0x09;
+pub const NUM_LOCK: u8 = 0x77;
+pub const SCROLL_LOCK: u8 = 0x7e;
+pub const NUMPAD_7: u8 = 0x6c;
+pub const NUMPAD_8: u8 = 0x75;
+pub const NUMPAD_9: u8 = 0x7d;
+pub const NUMPAD_MINUS: u8 = 0x7b;
+pub const NUMPAD_4: u8 = 0x6b;
+pub const NUMPAD_5: u8 = 0x73;
+pub const NUMPAD_6: u8 = 0x74;
+pub const NUMPAD_PLUS: u8 = 0x79;
+pub const NUMPAD_1: u8 = 0x69;
+pub const NUMPAD_2: u8 = 0x72;
+pub const NUMPAD_3: u8 = 0x7a;
+pub const NUMPAD_0: u8 = 0x70;
+pub const NUMPAD_PERIOD: u8 = 0x71;
+pub const F11: u8 = 0x78;
+pub const F12: u8 = 0x07;
+
+/// The make code sequence sent when Print Screen is pressed.
+pub const PRINT_SCREEN_PRESSED: [u8; 4] = [0xe0, 0x12, 0xe0, 0x7c];
+/// The break code sequence sent when Print Screen is released.
+pub const PRINT_SCREEN_RELEASED: [u8; 6] = [0xe0, 0xf0, 0x7c, 0xe0, 0xf0, 0x12];
+/// The sequence sent when Pause is pressed. Pause sends no break code when released.
+pub const PAUSE_PRESSED: [u8; 8] = [0xe1, 0x14, 0x77, 0xe1, 0xf0, 0x14, 0xf0, 0x77];
+
+/// Make codes of extended keys, which are sent after
+/// [`EXTENDED_PREFIX`](crate::scancodes::EXTENDED_PREFIX).
+pub mod extended {
+    pub const NUMPAD_ENTER: u8 = 0x5a;
+    pub const RIGHT_CONTROL: u8 = 0x14;
+    pub const NUMPAD_SLASH: u8 = 0x4a;
+    pub const RIGHT_ALT: u8 = 0x11;
+    pub const HOME: u8 = 0x6c;
+    pub const UP: u8 = 0x75;
+    pub const PAGE_UP: u8 = 0x7d;
+    pub const LEFT: u8 = 0x6b;
+    pub const RIGHT: u8 = 0x74;
+    pub const END: u8 = 0x69;
+    pub const DOWN: u8 = 0x72;
+    pub const PAGE_DOWN: u8 = 0x7a;
+    pub const INSERT: u8 = 0x70;
+    pub const DELETE: u8 = 0x71;
+    pub const LEFT_GUI: u8 = 0x1f;
+    pub const RIGHT_GUI: u8 = 0x27;
+    pub const APPS: u8 = 0x2f;
+}
@@ -0,0 +1,198 @@
+use crate::scancodes::{set1, set2};
+
+/// Pairs of set 2 and set 1 make codes for each single-byte key.
+const KEYS: [(u8, u8); 85] = [
+    (set2::ESCAPE, set1::ESCAPE),
+    (set2::KEY_1, set1::KEY_1),
+    (set2::KEY_2, set1::KEY_2),
+    (set2::KEY_3, set1::KEY_3),
+    (set2::KEY_4, set1::KEY_4),
+    (set2::KEY_5, set1::KEY_5),
+    (set2::KEY_6, set1::KEY_6),
+    (set2::KEY_7, set1::KEY_7),
+    (set2::KEY_8, set1::KEY_8),
+    (set2::KEY_9, set1::KEY_9),
+    (set2::KEY_0, set1::KEY_0),
+    (set2::MINUS, set1::MINUS),
+    (set2::EQUALS, set1::EQUALS),
+    (set2::BACKSPACE, set1::BACKSPACE),
+    (set2::TAB, set1::TAB),
+    (set2::Q, set1::Q),
+    (set2::W, set1::W),
+    (set2::E, set1::E),
+    (set2::R, set1::R),
+    (set2::T, set1::T),
+    (set2::Y, set1::Y),
+    (set2::U, set1::U),
+    (set2::I, set1::I),
+    (set2::O, set1::O),
+    (set2::P, set1::P),
+    (set2::LEFT_BRACKET, set1::LEFT_BRACKET),
+    (set2::RIGHT_BRACKET, set1::RIGHT_BRACKET),
+    (set2::ENTER, set1::ENTER),
+    (set2::LEFT_CONTROL, set1::LEFT_CONTROL),
+    (set2::A, set1::A),
+    (set2::S, set1::S),
+    (set2::D, set1::D),
+    (set2::F, set1::F),
+    (set2::G, set1::G),
+    (set2::H, set1::H),
+    (set2::J, set1::J),
+    (set2::K, set1::K),
+    (set2::L, set1::L),
+    (set2::SEMICOLON, set1::SEMICOLON),
+    (set2::APOSTROPHE, set1::APOSTROPHE),
+    (set2::BACKTICK, set1::BACKTICK),
+    (set2::LEFT_SHIFT, set1::LEFT_SHIFT),
+    (set2::BACKSLASH, set1::BACKSLASH),
+    (set2::Z, set1::Z),
+    (set2::X, set1::X),
+    (set2::C, set1::C),
+    (set2::V, set1::V),
+    (set2::B, set1::B),
+    (set2::N, set1::N),
+    (set2::M, set1::M),
+    (set2::COMMA, set1::COMMA),
+    (set2::PERIOD, set1::PERIOD),
+    (set2::SLASH, set1::SLASH),
+    (set2::RIGHT_SHIFT, set1::RIGHT_SHIFT),
+    (set2::NUMPAD_ASTERISK, set1::NUMPAD_ASTERISK),
+    (set2::LEFT_ALT, set1::LEFT_ALT),
+    (set2::SPACE, set1::SPACE),
+    (set2::CAPS_LOCK, set1::CAPS_LOCK),
+    (set2::F1, set1::F1),
+    (set2::F2, set1::F2),
+    (set2::F3, set1::F3),
+    (set2::F4, set1::F4),
+    (set2::F5, set1::F5),
+    (set2::F6, set1::F6),
+    (set2::F7, set1::F7),
+    (set2::F8, set1::F8),
+    (set2::F9, set1::F9),
+    (set2::F10, set1::F10),
+    (set2::NUM_LOCK, set1::NUM_LOCK),
+    (set2::SCROLL_LOCK, set1::SCROLL_LOCK),
+    (set2::NUMPAD_7, set1::NUMPAD_7),
+    (set2::NUMPAD_8, set1::NUMPAD_8),
+    (set2::NUMPAD_9, set1::NUMPAD_9),
+    (set2::NUMPAD_MINUS, set1::NUMPAD_MINUS),
+    (set2::NUMPAD_4, set1::NUMPAD_4),
+    (set2::NUMPAD_5, set1::NUMPAD_5),
+    (set2::NUMPAD_6, set1::NUMPAD_6),
+    (set2::NUMPAD_PLUS, set1::NUMPAD_PLUS),
+    (set2::NUMPAD_1, set1::NUMPAD_1),
+    (set2::NUMPAD_2, set1::NUMPAD_2),
+    (set2::NUMPAD_3, set1::NUMPAD_3),
+    (set2::NUMPAD_0, set1::NUMPAD_0),
+    (set2::NUMPAD_PERIOD, set1::NUMPAD_PERIOD),
+    (set2::F11, set1::F11),
+    (set2::F12, set1::F12),
+];
+
+/// Pairs of set 2 and set 1 make codes for each extended key.
+const EXTENDED_KEYS: [(u8, u8); 17] = [
+    (set2::extended::NUMPAD_ENTER, set1::extended::NUMPAD_ENTER),
+    (set2::extended::RIGHT_CONTROL, set1::extended::RIGHT_CONTROL),
+    (set2::extended::NUMPAD_SLASH, set1::extended::NUMPAD_SLASH),
+    (set2::extended::RIGHT_ALT, set1::extended::RIGHT_ALT),
+    (set2::extended::HOME, set1::extended::HOME),
+    (set2::extended::UP, set1::extended::UP),
+    (set2::extended::PAGE_UP, set1::extended::PAGE_UP),
+    (set2::extended::LEFT, set1::extended::LEFT),
+    (set2::extended::RIGHT, set1::extended::RIGHT),
+    (set2::extended::END, set1::extended::END),
+    (set2::extended::DOWN, set1::extended::DOWN),
+    (set2::extended::PAGE_DOWN, set1::extended::PAGE_DOWN),
+    (set2::extended::INSERT, set1::extended::INSERT),
+    (set2::extended::DELETE, set1::extended::DELETE),
+    (set2::extended::LEFT_GUI, set1::extended::LEFT_GUI),
+    (set2::extended::RIGHT_GUI, set1::extended::RIGHT_GUI),
+    (set2::extended::APPS, set1::extended::APPS),
+];
+
+const TABLE_SIZE: usize = 0x84;
+
+static TABLE: [u8; TABLE_SIZE] = build_table(&KEYS);
+static EXTENDED_TABLE: [u8; TABLE_SIZE] = build_table(&EXTENDED_KEYS);
+
+/// Build a lookup table indexed by set 2 make code. Unused entries are 0, which is not the make
+/// code of any key in set 1.
+const fn build_table(keys: &[(u8, u8)]) -> [u8; TABLE_SIZE] {
+    let mut table = [0; TABLE_SIZE];
+    let mut i = 0;
+    while i < keys.len() {
+        table[keys[i].0 as usize] = keys[i].1;
+        i += 1;
+    }
+    table
+}
+
+fn lookup(table: &[u8; TABLE_SIZE], scancode: u8) -> Option<u8> {
+    match table.get(scancode as usize) {
+        Some(&0) | None => None,
+        Some(&translated) => Some(translated),
+    }
+}
+
+/// Converts scancodes from set 2 to set 1 in software, like the controller does when
+/// [`ControllerConfigFlags::ENABLE_TRANSLATE`](crate::flags::ControllerConfigFlags) is set.
+///
+/// Only make codes are translated. A set 2 break code is [`set2::BREAK_PREFIX`] followed by the
+/// make code, which becomes the translated make code passed to [`set1::break_code`]. The mapping
+/// is taken from [Andries Brouwer's "Keyboard scancodes"](https://web.archive.org/web/20200616182211/https://www.win.tue.nl/~aeb/linux/kbd/scancodes.html),
+/// and covers the keys of a 104-key US keyboard.
+///
+/// # Examples
+/// ```
+/// use ps2::scancodes::{set1, set2, ScancodeTranslator};
+///
+/// assert_eq!(ScancodeTranslator::translate_set2_to_set1(set2::A), Some(set1::A));
+/// ```
+#[derive(Debug)]
+pub struct ScancodeTranslator;
+
+impl ScancodeTranslator {
+    /// Translate the make code of a single-byte key from set 2 to set 1. Returns `None` if the
+    /// scancode doesn't belong to a known key.
+    pub fn translate_set2_to_set1(scancode: u8) -> Option<u8> {
+        lookup(&TABLE, scancode)
+    }
+
+    /// Translate the make code of an extended key, which follows
+    /// [`EXTENDED_PREFIX`](crate::scancodes::EXTENDED_PREFIX), from set 2 to set 1. Returns `None`
+    /// if the scancode doesn't belong to a known extended key.
+    pub fn translate_extended_set2_to_set1(scancode: u8) -> Option<u8> {
+        lookup(&EXTENDED_TABLE, scancode)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn translate_test() {
+        for &(set2, set1) in KEYS.iter() {
+            assert_eq!(ScancodeTranslator::translate_set2_to_set1(set2), Some(set1));
+        }
+        assert_eq!(ScancodeTranslator::translate_set2_to_set1(0x83), Some(0x41));
+        assert_eq!(ScancodeTranslator::translate_set2_to_set1(0x00), None);
+        assert_eq!(ScancodeTranslator::translate_set2_to_set1(0xf0), None);
+    }
+
+    #[test]
+    fn translate_extended_test() {
+        assert_eq!(
+            ScancodeTranslator::translate_extended_set2_to_set1(set2::extended::RIGHT_CONTROL),
+            Some(set1::extended::RIGHT_CONTROL)
+        );
+        assert_eq!(
+            ScancodeTranslator::translate_extended_set2_to_set1(0x00),
+            None
+        );
+        assert_eq!(
+            ScancodeTranslator::translate_extended_set2_to_set1(set2::A),
+            None
+        );
+    }
+}
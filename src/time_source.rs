@@ -0,0 +1,36 @@
+/// A source of elapsed time used to bound how long the controller waits for IO.
+///
+/// The [`Controller`](crate::Controller) compares the values returned by this trait against its
+/// configured timeout, so the timeout is expressed in whatever unit the time source counts in.
+/// Implement this on top of a hardware timer if polling cycles are too unreliable a measure of
+/// time on your platform.
+pub trait TimeSource {
+    /// Return the number of cycles elapsed since some fixed point in the past.
+    ///
+    /// Only the difference between two values is ever used, so the starting point is arbitrary.
+    /// The counter may wrap around.
+    fn elapsed_cycles(&mut self) -> u64;
+}
+
+/// The default [`TimeSource`], which counts every call as a single cycle.
+///
+/// Since the controller checks the time source once per status poll, this makes the timeout
+/// equal to the number of times an IO operation will be attempted.
+#[derive(Debug, Default)]
+pub struct CycleCounter {
+    cycles: u64,
+}
+
+impl CycleCounter {
+    /// Create a new cycle counter starting at zero.
+    pub const fn new() -> Self {
+        Self { cycles: 0 }
+    }
+}
+
+impl TimeSource for CycleCounter {
+    fn elapsed_cycles(&mut self) -> u64 {
+        self.cycles = self.cycles.wrapping_add(1);
+        self.cycles
+    }
+}